@@ -0,0 +1,154 @@
+use crate::float::Float;
+use crate::{Flt, Mesh, Vct};
+use std::fmt;
+
+// 轴对齐包围盒，lo 与 hi 为两个对角
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb<T = Flt> {
+    pub lo: Vct<T>,
+    pub hi: Vct<T>,
+}
+
+impl<T: Float> Aabb<T> {
+    pub fn new(lo: Vct<T>, hi: Vct<T>) -> Self {
+        Self { lo, hi }
+    }
+
+    // 不含任何点的包围盒，加入第一个点后即为该点
+    pub fn empty() -> Self {
+        Self::new(Vct::one() * T::INFINITY, Vct::one() * T::NEG_INFINITY)
+    }
+
+    pub fn from_points(pts: impl IntoIterator<Item = Vct<T>>) -> Self {
+        let mut ret = Self::empty();
+        pts.into_iter().for_each(|p| ret.add(p));
+        ret
+    }
+
+    pub fn cast<U: Float>(&self) -> Aabb<U> {
+        Aabb::new(self.lo.cast(), self.hi.cast())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo.x > self.hi.x || self.lo.y > self.hi.y || self.lo.z > self.hi.z
+    }
+
+    pub fn add(&mut self, p: Vct<T>) {
+        self.lo = self.lo.min(p);
+        self.hi = self.hi.max(p);
+    }
+
+    pub fn union(&self, rhs: &Self) -> Self {
+        Self::new(self.lo.min(rhs.lo), self.hi.max(rhs.hi))
+    }
+
+    pub fn size(&self) -> Vct<T> {
+        self.hi - self.lo
+    }
+
+    pub fn center(&self) -> Vct<T> {
+        (self.lo + self.hi) * T::from_f64(0.5)
+    }
+
+    pub fn diagonal(&self) -> T {
+        self.size().len()
+    }
+
+    pub fn longest_side(&self) -> T {
+        let d = self.size();
+        d.x.max(d.y).max(d.z)
+    }
+
+    // 最长边所在的轴，相等时取靠前的轴
+    pub fn longest_axis(&self) -> usize {
+        let d = self.size();
+        if d.x >= d.y && d.x >= d.z {
+            0
+        } else if d.y >= d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn contains(&self, p: Vct<T>) -> bool {
+        self.lo.min(p) == self.lo && self.hi.max(p) == self.hi
+    }
+
+    pub fn overlaps(&self, rhs: &Self) -> bool {
+        self.lo.x <= rhs.hi.x
+            && rhs.lo.x <= self.hi.x
+            && self.lo.y <= rhs.hi.y
+            && rhs.lo.y <= self.hi.y
+            && self.lo.z <= rhs.hi.z
+            && rhs.lo.z <= self.hi.z
+    }
+
+    // 点到包围盒距离的平方，在盒内为 0
+    pub fn distance2(&self, p: Vct<T>) -> T {
+        let d = (self.lo - p).max(p - self.hi).max(Vct::zero());
+        d.len2()
+    }
+
+    // 射线 o + t d（inv 为 d 各分量的倒数）与包围盒相交的 t 的范围中最小的值，
+    // 要求 t 在 [0, t_max] 之内
+    pub fn ray(&self, o: Vct<T>, inv: Vct<T>, t_max: T) -> Option<T> {
+        let (a, b) = ((self.lo - o) * inv, (self.hi - o) * inv);
+        let (near, far) = (a.min(b), a.max(b));
+        let t0 = near.x.max(near.y).max(near.z).max(T::ZERO);
+        let t1 = far.x.min(far.y).min(far.z).min(t_max);
+        if t0 <= t1 {
+            Some(t0)
+        } else {
+            None
+        }
+    }
+
+    // 各边向外扩大 margin
+    pub fn expand(&self, margin: T) -> Self {
+        Self::new(self.lo - margin, self.hi + margin)
+    }
+}
+
+impl<T: Float> fmt::Display for Aabb<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} - {}", self.lo, self.hi)
+    }
+}
+
+impl<T: Float> Mesh<T> {
+    pub fn bbox(&self) -> Aabb<T> {
+        Aabb::from_points(self.pos.iter().cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aabb() {
+        let mut b = Aabb::empty();
+        assert!(b.is_empty());
+        b.add(Vct::new(1.0, 2.0, 3.0));
+        assert_eq!(b, Aabb::new(Vct::new(1.0, 2.0, 3.0), Vct::new(1.0, 2.0, 3.0)));
+        b.add(Vct::new(-1.0, 4.0, 3.5));
+        assert_eq!((b.size(), b.center()), (Vct::new(2.0, 2.0, 0.5), Vct::new(0.0, 3.0, 3.25)));
+        assert_eq!((b.longest_axis(), b.longest_side()), (0, 2.0));
+        assert!(b.contains(Vct::new(0.0, 3.0, 3.0)) && !b.contains(Vct::new(0.0, 1.0, 3.0)));
+        let c = Aabb::new(Vct::new(0.5, 0.0, 0.0), Vct::new(2.0, 2.0, 3.0));
+        assert!(b.overlaps(&c) && !b.overlaps(&c.expand(-0.5)));
+        assert_eq!(b.union(&c), Aabb::new(Vct::new(-1.0, 0.0, 0.0), Vct::new(2.0, 4.0, 3.5)));
+        assert_eq!(
+            (c.distance2(Vct::new(1.0, 1.0, 1.0)), c.distance2(Vct::new(0.0, 3.0, 5.0))),
+            (0.0, 5.25)
+        );
+        let inv = Vct::new(1.0, 1.0, 1.0) / Vct::new(1.0, 0.0, 0.0);
+        assert_eq!(c.ray(Vct::new(-1.0, 1.0, 1.0), inv, 10.0), Some(1.5));
+        assert_eq!(c.ray(Vct::new(-1.0, 1.0, 1.0), inv, 1.0), None);
+        assert_eq!(c.ray(Vct::new(-1.0, 3.0, 1.0), inv, 10.0), None);
+        let cube = Mesh::new("assets/cube.obj").bbox();
+        assert_eq!(cube, Aabb::new(Vct::zero(), Vct::one()));
+        assert_eq!(cube.cast::<f32>(), Aabb::new(Vct::zero(), Vct::one()));
+    }
+}
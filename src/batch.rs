@@ -0,0 +1,326 @@
+use crate::json::Json;
+use crate::{Distance, Flt, Format, Mesh, SimplifyOptions, VertexSource};
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+// 一个文件的处理结果，失败时 error 为错误信息，输出的规模与误差无意义
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileReport {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub error: Option<String>,
+    pub in_vertices: usize,
+    pub in_faces: usize,
+    pub out_vertices: usize,
+    pub out_faces: usize,
+    // 读入、简化、保存与测量误差的总用时（秒）与其中简化的用时
+    pub seconds: Flt,
+    pub simplify_seconds: Flt,
+    // 执行过的收缩的最大代价
+    pub max_cost: Flt,
+    // 输入与输出之间的双向表面距离，只在要求测量时计算
+    pub distance: Option<Distance>,
+}
+
+const CSV_HEADER: &str = "input,output,status,error,in_vertices,in_faces,out_vertices,out_faces,\
+                          seconds,simplify_seconds,max_cost,mean_distance,rms_distance,hausdorff";
+
+impl FileReport {
+    pub fn json(&self) -> Json {
+        let d = |f: fn(&Distance) -> Flt| Json::from(self.distance.as_ref().map(f));
+        let fields: Vec<(&str, Json)> = vec![
+            ("input", self.input.display().to_string().into()),
+            ("output", self.output.display().to_string().into()),
+            ("status", if self.error.is_none() { "ok" } else { "failed" }.into()),
+            ("error", self.error.clone().into()),
+            ("in_vertices", self.in_vertices.into()),
+            ("in_faces", self.in_faces.into()),
+            ("out_vertices", self.out_vertices.into()),
+            ("out_faces", self.out_faces.into()),
+            ("seconds", self.seconds.into()),
+            ("simplify_seconds", self.simplify_seconds.into()),
+            ("max_cost", self.max_cost.into()),
+            ("mean_distance", d(|d| d.mean)),
+            ("rms_distance", d(|d| d.rms)),
+            ("hausdorff", d(|d| d.max)),
+        ];
+        Json::Obj(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    fn csv(&self) -> String {
+        let field = |j: &Json| match j {
+            Json::Null => String::new(),
+            Json::Str(s) if s.contains([',', '"', '\n']) => {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            },
+            Json::Str(s) => s.clone(),
+            j => j.to_string(),
+        };
+        match self.json() {
+            Json::Obj(o) => o.iter().map(|(_, v)| field(v)).collect::<Vec<_>>().join(","),
+            _ => unreachable!(),
+        }
+    }
+}
+
+pub fn report_json(reports: &[FileReport]) -> Json {
+    let failed = reports.iter().filter(|r| r.error.is_some()).count();
+    Json::Obj(vec![
+        ("files".to_string(), reports.len().into()),
+        ("failed".to_string(), failed.into()),
+        ("seconds".to_string(), reports.iter().map(|r| r.seconds).sum::<Flt>().into()),
+        ("results".to_string(), Json::Arr(reports.iter().map(|r| r.json()).collect())),
+    ])
+}
+
+pub fn report_csv(reports: &[FileReport]) -> String {
+    let mut s = CSV_HEADER.to_string() + "\n";
+    reports.iter().for_each(|r| s += &(r.csv() + "\n"));
+    s
+}
+
+// 通配符：* 与 ? 不跨越 /，** 匹配任意层目录
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn go(p: &[u8], s: &[u8]) -> bool {
+        match p.first() {
+            None => s.is_empty(),
+            Some(b'*') if p.get(1) == Some(&b'*') => {
+                let rest = p[2..].strip_prefix(b"/").unwrap_or(&p[2..]);
+                rest.is_empty()
+                    || (0..=s.len())
+                        .filter(|&i| i == 0 || s[i - 1] == b'/')
+                        .any(|i| go(rest, &s[i..]))
+            },
+            Some(b'*') => (0..=s.len())
+                .take_while(|&i| i == 0 || s[i - 1] != b'/')
+                .any(|i| go(&p[1..], &s[i..])),
+            Some(b'?') => s.first().is_some_and(|&c| c != b'/') && go(&p[1..], &s[1..]),
+            Some(&c) => s.first() == Some(&c) && go(&p[1..], &s[1..]),
+        }
+    }
+    go(pattern.as_bytes(), path.as_bytes())
+}
+
+// dir 下所有 OBJ 与 PLY 文件相对于 dir 的路径，按字典序排列
+fn walk(dir: &Path, rel: &Path, out: &mut Vec<PathBuf>) {
+    let mut entries: Vec<_> = match fs::read_dir(dir.join(rel)) {
+        Ok(rd) => rd.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(_) => return,
+    };
+    entries.sort();
+    for p in entries {
+        let r = rel.join(p.file_name().unwrap());
+        if p.is_dir() {
+            walk(dir, &r, out);
+        } else if Format::from_ext(&p.to_string_lossy()).is_some() {
+            out.push(r);
+        }
+    }
+}
+
+// 把文件、目录或通配符展开成 (输入文件, 输出时相对于输出目录的路径)：
+// 目录与通配符中不含通配符的前缀部分之下的目录结构会被保留
+pub fn collect_inputs(patterns: &[String]) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let mut ret = vec![];
+    for pat in patterns {
+        let path = Path::new(pat);
+        if path.is_file() {
+            if !ret.iter().any(|(i, _)| i == path) {
+                ret.push((path.to_path_buf(), PathBuf::from(path.file_name().unwrap())));
+            }
+            continue;
+        }
+        let (mut base, mut rest) = (PathBuf::new(), vec![]);
+        for c in path.components() {
+            let c = c.as_os_str().to_string_lossy();
+            if rest.is_empty() && !c.contains(['*', '?']) {
+                base.push(c.as_ref());
+            } else {
+                rest.push(c.to_string());
+            }
+        }
+        let rest = match (rest.is_empty(), base.is_dir()) {
+            (true, true) => "**".to_string(),
+            (false, true) => rest.join("/"),
+            _ => return Err(format!("no such file or directory: {}", pat)),
+        };
+        let mut files = vec![];
+        walk(&base, Path::new(""), &mut files);
+        let matched: Vec<_> = files
+            .into_iter()
+            .filter(|f| glob_match(&rest, &f.to_string_lossy().replace('\\', "/")))
+            .collect();
+        if matched.is_empty() {
+            return Err(format!("no mesh files match {}", pat));
+        }
+        for f in matched {
+            let input = base.join(&f);
+            if !ret.iter().any(|(i, _)| *i == input) {
+                ret.push((input, f));
+            }
+        }
+    }
+    Ok(ret)
+}
+
+fn simplify_file<F>(
+    input: &Path,
+    output: &Path,
+    format: Option<Format>,
+    measure: bool,
+    opts: &F,
+) -> Result<FileReport, String>
+where
+    F: Fn(&Mesh, &VertexSource) -> Result<SimplifyOptions, String>,
+{
+    let start = Instant::now();
+    let mut r = FileReport {
+        input: input.to_path_buf(),
+        output: output.to_path_buf(),
+        ..Default::default()
+    };
+    let (mesh, source) = Mesh::try_load_indexed(&input.to_string_lossy())?;
+    r.in_vertices = mesh.pos.len();
+    r.in_faces = mesh.tri.len();
+    let opts = opts(&mesh, &source)?;
+    opts.validate(mesh.pos.len())?;
+    let t = Instant::now();
+    let ret = mesh.simplify_detailed(&opts);
+    r.simplify_seconds = t.elapsed().as_secs_f64();
+    r.out_vertices = ret.mesh.pos.len();
+    r.out_faces = ret.mesh.tri.len();
+    r.max_cost = ret.collapses.iter().fold(0.0, |a, c| c.cost.max(a));
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    }
+    let path = output.to_string_lossy();
+    ret.mesh
+        .try_save_as(&path, format.or_else(|| Format::from_ext(&path)).unwrap_or(Format::Obj))?;
+    if measure {
+        r.distance = Some(mesh.hausdorff(&ret.mesh, 1));
+    }
+    r.seconds = start.elapsed().as_secs_f64();
+    Ok(r)
+}
+
+// 用 jobs 个线程依次简化 files 中的 (输入, 输出)，某个文件失败不影响其它文件；
+// opts 由读入的网格给出简化参数（通常是事先解析好的参数加上逐顶点的标记），出错时这个文件失败；
+// done 在每个文件处理完后被调用
+pub fn simplify_batch<F, D>(
+    files: &[(PathBuf, PathBuf)],
+    jobs: usize,
+    format: Option<Format>,
+    measure: bool,
+    opts: F,
+    done: D,
+) -> Vec<FileReport>
+where
+    F: Fn(&Mesh, &VertexSource) -> Result<SimplifyOptions, String> + Sync,
+    D: Fn(&FileReport) + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(vec![FileReport::default(); files.len()]);
+    thread::scope(|s| {
+        for _ in 0..jobs.max(1).min(files.len()) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let (input, output) = match files.get(i) {
+                    Some(f) => f,
+                    None => break,
+                };
+                let start = Instant::now();
+                // 错误由 simplify_file 返回；库中意外的 panic 也只记为这个文件失败
+                let r = panic::catch_unwind(AssertUnwindSafe(|| {
+                    simplify_file(input, output, format, measure, &opts)
+                }))
+                .unwrap_or_else(|e| {
+                    Err(match e.downcast_ref::<&str>() {
+                        Some(s) => s.to_string(),
+                        None => e.downcast_ref::<String>().cloned().unwrap_or_default(),
+                    })
+                })
+                .unwrap_or_else(|e| FileReport {
+                    input: input.clone(),
+                    output: output.clone(),
+                    error: Some(e),
+                    seconds: start.elapsed().as_secs_f64(),
+                    ..Default::default()
+                });
+                done(&r);
+                reports.lock().unwrap()[i] = r;
+            });
+        }
+    });
+    reports.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*.obj", "a.obj"));
+        assert!(!glob_match("*.obj", "d/a.obj"));
+        assert!(glob_match("**/*.obj", "a.obj"));
+        assert!(glob_match("**/*.obj", "d/e/a.obj"));
+        assert!(glob_match("d/**", "d/e/a.ply"));
+        assert!(glob_match("?/a.*", "d/a.ply"));
+        assert!(!glob_match("?/a.*", "dd/a.ply"));
+    }
+
+    #[test]
+    fn batch() {
+        let root = std::env::temp_dir().join("mesh_simplification_batch");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("in/sub")).unwrap();
+        fs::copy("assets/cube.obj", root.join("in/cube.obj")).unwrap();
+        let sphere = Mesh::new("assets/sphere.obj").simplify(0.1);
+        sphere.save(&root.join("in/sub/sphere.obj").to_string_lossy());
+        fs::write(root.join("in/sub/broken.obj"), "v 0 0 0\nf 1 2 3\n").unwrap();
+        fs::write(root.join("in/notes.txt"), "").unwrap();
+        let files = collect_inputs(&[root.join("in").to_string_lossy().to_string()]).unwrap();
+        let rel: Vec<_> = files.iter().map(|f| f.1.clone()).collect();
+        assert_eq!(rel, ["cube.obj", "sub/broken.obj", "sub/sphere.obj"].map(PathBuf::from));
+        let pat = root.join("in/**/s*.obj").to_string_lossy().to_string();
+        assert_eq!(collect_inputs(&[pat.clone(), pat]).unwrap().len(), 1);
+        assert!(collect_inputs(&[root.join("in/*.ply").to_string_lossy().to_string()]).is_err());
+
+        let files: Vec<_> = files
+            .into_iter()
+            .map(|(i, r)| (i, root.join("out").join(r).with_extension("ply")))
+            .collect();
+        let count = AtomicUsize::new(0);
+        let reports = simplify_batch(
+            &files,
+            2,
+            Some(Format::Ply),
+            true,
+            |mesh, _| match mesh.tri.len() {
+                12 => Err("cube rejected".to_string()),
+                _ => Ok(SimplifyOptions { ratio: 0.1, ..Default::default() }),
+            },
+            |_| {
+                count.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(reports[0].error.as_deref(), Some("cube rejected"));
+        assert!(reports[1].error.as_ref().unwrap().contains("missing vertex"));
+        let sphere = &reports[2];
+        assert!(sphere.error.is_none() && sphere.out_faces * 9 < sphere.in_faces);
+        assert!(sphere.distance.unwrap().max > 0.0);
+        let path = root.join("out/sub/sphere.ply").to_string_lossy().to_string();
+        assert_eq!(Format::detect(&path), Format::Ply);
+        assert_eq!(Mesh::new(&path).tri.len(), sphere.out_faces);
+        assert_eq!(report_json(&reports).get("failed"), Some(&Json::Num(2.0)));
+        let csv = report_csv(&reports);
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.lines().all(|l| l.split(',').count() >= CSV_HEADER.split(',').count()));
+    }
+}
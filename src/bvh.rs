@@ -0,0 +1,346 @@
+use crate::mesh::Tri;
+use crate::stats::closest_on_tri;
+use crate::{Aabb, Flt, Mesh, Vct};
+use std::cmp::Ordering;
+
+// 叶子中最多的三角形数
+const LEAF: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    bbox: Aabb,
+    // 叶子中的三角形为 order[start..start + count]
+    start: usize,
+    count: usize,
+    // 两个孩子为 child 与 child + 1，叶子为 0
+    child: usize,
+}
+
+// 网格三角形的包围盒层次结构，保存一份顶点与面，顶点移动后用 refit 更新包围盒
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    pos: Vec<Vct>,
+    tri: Vec<Tri>,
+    order: Vec<usize>,
+    nodes: Vec<Node>,
+}
+
+// 曲面上离查询点最近的点，face 为所在的面
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Nearest {
+    pub face: usize,
+    pub point: Vct,
+    pub dist: Flt,
+}
+
+// 射线 o + t d 与面 face 的交点，重心坐标为 (1 - u - v, u, v)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub face: usize,
+    pub t: Flt,
+    pub u: Flt,
+    pub v: Flt,
+}
+
+// Möller–Trumbore，不区分正反面
+fn ray_tri(o: Vct, d: Vct, a: Vct, b: Vct, c: Vct) -> Option<(Flt, Flt, Flt)> {
+    let (e1, e2) = (b - a, c - a);
+    let p = d % e2;
+    let det = e1.dot(p);
+    if det == 0.0 {
+        return None;
+    }
+    let s = o - a;
+    let u = s.dot(p) / det;
+    let q = s % e1;
+    let v = d.dot(q) / det;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some((e2.dot(q) / det, u, v))
+}
+
+// 分离轴定理：包围盒的三个面法向、三角形法向以及两两边方向的叉积
+fn tri_box(bbox: &Aabb, a: Vct, b: Vct, c: Vct) -> bool {
+    if !bbox.overlaps(&Aabb::from_points([a, b, c].iter().cloned())) {
+        return false;
+    }
+    let (o, h) = (bbox.center(), bbox.size() * 0.5);
+    let v = [a - o, b - o, c - o];
+    let e = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+    let unit = [Vct::new(1.0, 0.0, 0.0), Vct::new(0.0, 1.0, 0.0), Vct::new(0.0, 0.0, 1.0)];
+    let separated = |n: Vct| {
+        let r = h.dot(n.abs());
+        let p = v.iter().map(|x| x.dot(n));
+        let (lo, hi) = p.fold((Flt::INFINITY, Flt::NEG_INFINITY), |(l, h), x| (l.min(x), h.max(x)));
+        lo > r || hi < -r
+    };
+    if separated(e[0] % e[1]) {
+        return false;
+    }
+    !e.iter().any(|&e| unit.iter().any(|&u| separated(e % u)))
+}
+
+impl Bvh {
+    pub fn new(mesh: &Mesh) -> Self {
+        let mut bvh = Self {
+            pos: mesh.pos.clone(),
+            tri: mesh.tri.clone(),
+            order: (0..mesh.tri.len()).collect(),
+            nodes: vec![],
+        };
+        if !bvh.tri.is_empty() {
+            let cent: Vec<_> = (0..bvh.tri.len()).map(|i| bvh.corners(i)).collect();
+            let cent: Vec<_> = cent.iter().map(|&[a, b, c]| (a + b + c) / 3.0).collect();
+            bvh.nodes.push(Node { bbox: Aabb::empty(), start: 0, count: 0, child: 0 });
+            bvh.build(0, 0, bvh.tri.len(), &cent);
+        }
+        bvh
+    }
+
+    fn corners(&self, i: usize) -> [Vct; 3] {
+        let (a, b, c) = self.tri[i];
+        [self.pos[a], self.pos[b], self.pos[c]]
+    }
+
+    fn leaf_box(&self, start: usize, end: usize) -> Aabb {
+        Aabb::from_points(self.order[start..end].iter().flat_map(|&i| self.corners(i)))
+    }
+
+    // 沿三角形重心包围盒的最长轴按中位数分成两半
+    fn build(&mut self, node: usize, start: usize, end: usize, cent: &[Vct]) {
+        self.nodes[node].bbox = self.leaf_box(start, end);
+        if end - start <= LEAF {
+            self.nodes[node].start = start;
+            self.nodes[node].count = end - start;
+            return;
+        }
+        let axis =
+            Aabb::from_points(self.order[start..end].iter().map(|&i| cent[i])).longest_axis();
+        let mid = (start + end) / 2;
+        self.order[start..end].select_nth_unstable_by(mid - start, |&a, &b| {
+            cent[a][axis].partial_cmp(&cent[b][axis]).unwrap_or(Ordering::Equal)
+        });
+        let child = self.nodes.len();
+        let empty = Node { bbox: Aabb::empty(), start: 0, count: 0, child: 0 };
+        self.nodes.extend([empty, empty].iter());
+        self.nodes[node].child = child;
+        self.build(child, start, mid, cent);
+        self.build(child + 1, mid, end, cent);
+    }
+
+    // 顶点移动（数目与面不变）之后重新计算包围盒，树的结构不变
+    pub fn refit(&mut self, pos: &[Vct]) {
+        assert_eq!(pos.len(), self.pos.len());
+        self.pos.copy_from_slice(pos);
+        // 孩子总在父亲之后
+        for i in (0..self.nodes.len()).rev() {
+            let Node { start, count, child, .. } = self.nodes[i];
+            self.nodes[i].bbox = match child {
+                0 => self.leaf_box(start, start + count),
+                _ => self.nodes[child].bbox.union(&self.nodes[child + 1].bbox),
+            };
+        }
+    }
+
+    // 依次访问包围盒满足 enter 的叶子中的三角形
+    fn visit(&self, enter: impl Fn(&Aabb) -> bool, mut f: impl FnMut(usize)) {
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(i) = stack.pop() {
+            let n = &self.nodes[i];
+            if !enter(&n.bbox) {
+                continue;
+            }
+            match n.child {
+                0 => self.order[n.start..n.start + n.count].iter().for_each(|&t| f(t)),
+                c => stack.extend([c, c + 1].iter()),
+            }
+        }
+    }
+
+    pub fn closest_point(&self, p: Vct) -> Option<Nearest> {
+        let mut best: Option<Nearest> = None;
+        let mut best2 = Flt::INFINITY;
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![(0, 0.0)] };
+        while let Some((i, d2)) = stack.pop() {
+            if d2 >= best2 {
+                continue;
+            }
+            let n = &self.nodes[i];
+            if n.child == 0 {
+                for &t in &self.order[n.start..n.start + n.count] {
+                    let [a, b, c] = self.corners(t);
+                    let q = closest_on_tri(p, a, b, c);
+                    let d2 = (q - p).len2();
+                    if d2 < best2 {
+                        best2 = d2;
+                        best = Some(Nearest { face: t, point: q, dist: 0.0 });
+                    }
+                }
+                continue;
+            }
+            // 近的孩子后入栈，先被访问
+            let (l, r) = (n.child, n.child + 1);
+            let (dl, dr) = (self.nodes[l].bbox.distance2(p), self.nodes[r].bbox.distance2(p));
+            if dl < dr {
+                stack.extend([(r, dr), (l, dl)].iter());
+            } else {
+                stack.extend([(l, dl), (r, dr)].iter());
+            }
+        }
+        best.map(|n| Nearest { dist: best2.sqrt(), ..n })
+    }
+
+    // t 在 [0, t_max] 之内最近的交点
+    pub fn ray(&self, o: Vct, d: Vct, t_max: Flt) -> Option<RayHit> {
+        let inv = Vct::one() / d;
+        let mut best: Option<RayHit> = None;
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(i) = stack.pop() {
+            let n = &self.nodes[i];
+            let limit = best.map_or(t_max, |h| h.t);
+            if n.bbox.ray(o, inv, limit).is_none() {
+                continue;
+            }
+            match n.child {
+                0 => {
+                    for &t in &self.order[n.start..n.start + n.count] {
+                        let [a, b, c] = self.corners(t);
+                        if let Some((s, u, v)) = ray_tri(o, d, a, b, c) {
+                            if s >= 0.0 && s <= best.map_or(t_max, |h| h.t) {
+                                best = Some(RayHit { face: t, t: s, u, v });
+                            }
+                        }
+                    }
+                },
+                c => stack.extend([c, c + 1].iter()),
+            }
+        }
+        best
+    }
+
+    // 与球有公共部分的面
+    pub fn sphere(&self, center: Vct, radius: Flt) -> Vec<usize> {
+        let r2 = radius * radius;
+        let mut ret = vec![];
+        self.visit(
+            |b| b.distance2(center) <= r2,
+            |t| {
+                let [a, b, c] = self.corners(t);
+                if (closest_on_tri(center, a, b, c) - center).len2() <= r2 {
+                    ret.push(t);
+                }
+            },
+        );
+        ret
+    }
+
+    // 与包围盒有公共部分的面
+    pub fn overlap(&self, bbox: &Aabb) -> Vec<usize> {
+        let mut ret = vec![];
+        self.visit(
+            |b| b.overlaps(bbox),
+            |t| {
+                let [a, b, c] = self.corners(t);
+                if tri_box(bbox, a, b, c) {
+                    ret.push(t);
+                }
+            },
+        );
+        ret
+    }
+}
+
+impl Mesh {
+    pub fn bvh(&self) -> Bvh {
+        Bvh::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mat, EPS};
+
+    fn points(n: usize, lo: Vct, hi: Vct) -> Vec<Vct> {
+        let mut seed: u64 = 7;
+        let mut rand = move || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 11) as Flt / (1u64 << 53) as Flt
+        };
+        (0..n).map(|_| lo + (hi - lo) * Vct::new(rand(), rand(), rand())).collect()
+    }
+
+    fn brute(mesh: &Mesh, p: Vct) -> Flt {
+        let d = mesh.tri.iter().map(|&(a, b, c)| {
+            (closest_on_tri(p, mesh.pos[a], mesh.pos[b], mesh.pos[c]) - p).len2()
+        });
+        d.fold(Flt::INFINITY, Flt::min).sqrt()
+    }
+
+    #[test]
+    fn closest_point() {
+        let mut mesh = Mesh::new("assets/sphere.obj");
+        let b = mesh.bbox().expand(0.5);
+        let mut bvh = mesh.bvh();
+        for &p in &points(200, b.lo, b.hi) {
+            let n = bvh.closest_point(p).unwrap();
+            assert!((n.dist - brute(&mesh, p)).abs() < EPS);
+            assert!(((n.point - p).len() - n.dist).abs() < EPS);
+        }
+        mesh.transform(&(Mat::translate(Vct::new(3.0, 0.0, 0.0)) * Mat::scale(Vct::one() * 0.5)));
+        bvh.refit(&mesh.pos);
+        for &p in &points(50, b.lo, b.hi) {
+            assert!((bvh.closest_point(p).unwrap().dist - brute(&mesh, p)).abs() < EPS);
+        }
+        assert!(Mesh::from_raw(vec![], vec![]).bvh().closest_point(Vct::zero()).is_none());
+    }
+
+    #[test]
+    fn ray() {
+        let mesh = Mesh::new("assets/cube.obj");
+        let bvh = mesh.bvh();
+        let o = Vct::new(0.25, 0.5, 3.0);
+        let hit = bvh.ray(o, Vct::new(0.0, 0.0, -2.0), Flt::INFINITY).unwrap();
+        assert!((hit.t - 1.0).abs() < EPS);
+        assert!(bvh.ray(o, Vct::new(0.0, 0.0, -1.0), 1.5).is_none());
+        assert!(bvh.ray(o, Vct::new(0.0, 0.0, 1.0), Flt::INFINITY).is_none());
+        // 从内部射出
+        let hit = bvh.ray(Vct::new(0.5, 0.3, 0.6), Vct::new(1.0, 0.0, 0.0), Flt::INFINITY).unwrap();
+        assert!((hit.t - 0.5).abs() < EPS);
+        let ((a, b, c), pos) = (mesh.tri[hit.face], &mesh.pos);
+        let p = pos[a] * (1.0 - hit.u - hit.v) + pos[b] * hit.u + pos[c] * hit.v;
+        assert!((p - Vct::new(1.0, 0.3, 0.6)).len() < EPS);
+    }
+
+    #[test]
+    fn overlap() {
+        let mesh = Mesh::new("assets/block.obj");
+        let bvh = mesh.bvh();
+        let b = mesh.bbox();
+        for (i, &p) in points(20, b.lo, b.hi).iter().enumerate() {
+            let r = b.diagonal() * 0.02 * (i % 4) as Flt;
+            let mut got = bvh.sphere(p, r);
+            got.sort_unstable();
+            let want: Vec<_> = (0..mesh.tri.len())
+                .filter(|&t| {
+                    let (x, y, z) = mesh.tri[t];
+                    (closest_on_tri(p, mesh.pos[x], mesh.pos[y], mesh.pos[z]) - p).len2() <= r * r
+                })
+                .collect();
+            assert_eq!(got, want);
+            let q = Aabb::new(p, p).expand(r);
+            let got = bvh.overlap(&q);
+            assert!(want.iter().all(|t| got.contains(t)));
+        }
+        // 盒子与三角形所在平面相交但不与三角形相交
+        let tri = Mesh::from_raw(
+            vec![Vct::zero(), Vct::new(1.0, 0.0, 0.0), Vct::new(0.0, 1.0, 0.0)],
+            vec![(0, 1, 2)],
+        );
+        let q = |lo: Vct| Aabb::new(lo, lo + 0.2);
+        assert_eq!(tri.bvh().overlap(&q(Vct::new(0.1, 0.1, -0.1))), vec![0]);
+        assert!(tri.bvh().overlap(&q(Vct::new(0.6, 0.6, -0.1))).is_empty());
+        assert!(tri.bvh().overlap(&q(Vct::new(0.1, 0.1, 0.1))).is_empty());
+    }
+}
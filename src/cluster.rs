@@ -0,0 +1,144 @@
+use crate::mesh::Tri;
+use crate::normal::NORMAL_CREASE;
+use crate::quadric::{corner_weights, Quadric};
+use crate::{Aabb, Flt, Mesh, NormalWeight, Vct};
+use std::collections::{HashMap, HashSet};
+
+// Cell 的边长必须是有限的正数，否则 cluster 会 panic
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClusterSize {
+    Cell(Flt),       // 格子边长
+    Vertices(usize), // 目标顶点数
+}
+
+fn key(lo: Vct, cell: Flt, p: Vct) -> (i64, i64, i64) {
+    let k = (p - lo) / cell;
+    (k.x.floor() as i64, k.y.floor() as i64, k.z.floor() as i64)
+}
+
+// 均匀网格上的顶点聚类：三角形的误差矩阵与简化时的构造相同，按 weight 在各个角上加权后累加到顶点所在的格子，
+// 三个顶点落在不同格子的三角形才保留（重复的在最后去掉），每个被保留的三角形用到的格子合并为误差最小的一个点；
+// 坐标都减去 lo 之后再累加，远离原点的网格的误差矩阵也不会损失精度
+pub(crate) struct Grid {
+    lo: Vct,
+    cell: Flt,
+    weight: NormalWeight,
+    id: HashMap<(i64, i64, i64), usize>,
+    cells: Vec<(Quadric, Vct, usize)>, // (误差矩阵, 顶点坐标和, 顶点个数)
+    tri: Vec<Tri>,
+}
+
+impl Grid {
+    pub fn new(lo: Vct, cell: Flt, weight: NormalWeight) -> Self {
+        assert!(cell.is_finite() && cell > 0.0, "the cell size must be positive, got {}", cell);
+        Self { lo, cell, weight, id: HashMap::new(), cells: vec![], tri: vec![] }
+    }
+
+    pub fn add(&mut self, p: [Vct; 3]) {
+        let p = p.map(|p| p - self.lo);
+        let t = p.map(|p| {
+            let cells = &mut self.cells;
+            *self.id.entry(key(Vct::zero(), self.cell, p)).or_insert_with(|| {
+                cells.push((Quadric::default(), Vct::zero(), 0));
+                cells.len() - 1
+            })
+        });
+        let q = Quadric::triangle(p[0], p[1], p[2]);
+        let w = corner_weights(p, self.weight);
+        for k in 0..3 {
+            let cell = &mut self.cells[t[k]];
+            cell.0 += q * w[k];
+            cell.1 += p[k];
+            cell.2 += 1;
+        }
+        if t[0] != t[1] && t[1] != t[2] && t[0] != t[2] {
+            // 旋转成最小下标在前，重复的三角形排序后相邻
+            let k = (0..3).min_by_key(|&k| t[k]).unwrap();
+            self.tri.push((t[k], t[(k + 1) % 3], t[(k + 2) % 3]));
+        }
+    }
+
+    pub fn finish(mut self) -> Mesh {
+        self.tri.sort_unstable();
+        self.tri.dedup();
+        // 只输出被三角形用到的格子，下标重新编号
+        let mut map = vec![usize::MAX; self.cells.len()];
+        let mut pos = vec![];
+        let mut remap = |c: usize| {
+            if map[c] == usize::MAX {
+                let (q, sum, n) = &self.cells[c];
+                map[c] = pos.len();
+                pos.push(q.optimal_near(*sum / *n as Flt) + self.lo);
+            }
+            map[c]
+        };
+        let tri = self.tri.iter().map(|&(a, b, c)| (remap(a), remap(b), remap(c))).collect();
+        Mesh::from_raw(pos, tri)
+    }
+}
+
+impl Mesh {
+    pub fn cluster(&self, size: ClusterSize, weight: NormalWeight) -> Self {
+        let Aabb { lo, hi } = self.bbox();
+        let cell = match size {
+            ClusterSize::Cell(cell) => cell,
+            ClusterSize::Vertices(n) => {
+                // 二分格子边长，使被顶点占据的格子数不超过 n
+                let count =
+                    |cell| self.pos.iter().map(|&p| key(lo, cell, p)).collect::<HashSet<_>>();
+                let (mut l, mut r) = (0.0, (hi - lo).len() * 2.0);
+                for _ in 0..30 {
+                    let m = (l + r) * 0.5;
+                    if count(m).len() > n {
+                        l = m;
+                    } else {
+                        r = m;
+                    }
+                }
+                // 所有顶点重合时任何边长都只有一个格子
+                if r > 0.0 {
+                    r
+                } else {
+                    1.0
+                }
+            },
+        };
+        let mut grid = Grid::new(lo, cell, weight);
+        self.tri.iter().for_each(|&(a, b, c)| grid.add([self.pos[a], self.pos[b], self.pos[c]]));
+        let mut ret = grid.finish();
+        if self.has_normals() {
+            ret.compute_normals(NormalWeight::Angle, Some(NORMAL_CREASE));
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 每个输出的顶点都被某个三角形用到
+    fn referenced(m: &Mesh) -> bool {
+        let mut used = vec![false; m.pos.len()];
+        m.tri.iter().for_each(|&(a, b, c)| [a, b, c].iter().for_each(|&v| used[v] = true));
+        used.into_iter().all(|u| u)
+    }
+
+    #[test]
+    fn cluster() {
+        let mesh = Mesh::new("assets/bunny.fine.obj");
+        let out = mesh.cluster(ClusterSize::Vertices(3000), NormalWeight::Uniform);
+        assert!(out.pos.len() <= 3000 && out.pos.len() > 2000);
+        assert!(referenced(&out));
+        let out = mesh.cluster(ClusterSize::Cell(0.01), NormalWeight::Area);
+        assert!(referenced(&out));
+        let d = |p: Vct| out.pos.iter().map(|&q| (p - q).len2()).fold(Flt::INFINITY, Flt::min);
+        assert!(mesh.pos.iter().step_by(100).all(|&p| d(p).sqrt() < 0.02));
+    }
+
+    #[test]
+    #[should_panic(expected = "cell size")]
+    fn bad_cell() {
+        Mesh::new("assets/cube.obj").cluster(ClusterSize::Cell(Flt::NAN), NormalWeight::Uniform);
+    }
+}
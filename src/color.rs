@@ -0,0 +1,137 @@
+use crate::quadric::Quadric;
+use crate::{Flt, Vct};
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+
+// 颜色的误差矩阵：每个通道 k 在面上是位置的线性函数 s(p) = g·p + d，
+// 新顶点取位置 v、颜色 c 时的误差为 Σ (g·v + d - c_k)^2，
+// 展开后由 Σ [g d]^T [g d]、Σ [g d] 与面数三部分组成
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ColorQuadric {
+    q: [Quadric; 3],
+    b: [[Flt; 4]; 3],
+    a: Flt,
+}
+
+impl ColorQuadric {
+    pub fn new(p: [Vct; 3], c: [Vct; 3]) -> Self {
+        let (e1, e2) = (p[1] - p[0], p[2] - p[0]);
+        let n = e1 % e2;
+        let mut ret = Self::default();
+        if n.len2() <= 0.0 {
+            return ret;
+        }
+        for (k, (q, b)) in ret.q.iter_mut().zip(ret.b.iter_mut()).enumerate() {
+            // 梯度 g 满足 g·e1 = s1 - s0，g·e2 = s2 - s0，g·n = 0
+            let (s1, s2) = (c[1][k] - c[0][k], c[2][k] - c[0][k]);
+            let g = ((e2 % n) * s1 + (n % e1) * s2) / n.len2();
+            *b = [g.x, g.y, g.z, c[0][k] - g.dot(p[0])];
+            *q = Quadric::from_plane(g, b[3]);
+        }
+        ret.a = 1.0;
+        ret
+    }
+
+    // 位置取 v 时误差最小的颜色
+    pub fn optimal(&self, v: Vct) -> Option<Vct> {
+        let s = |k: usize| (0..4).map(|i| self.b[k][i] * v[i]).sum::<Flt>() / self.a;
+        Some(Vct::new(s(0), s(1), s(2)).max(Vct::zero()).min(Vct::one())).filter(|_| self.a > 0.0)
+    }
+
+    pub fn error(&self, v: Vct, c: Vct) -> Flt {
+        (0..3)
+            .map(|k| {
+                let b = (0..4).map(|i| self.b[k][i] * v[i]).sum::<Flt>();
+                self.q[k].evaluate(v) - 2.0 * c[k] * b + c[k] * c[k] * self.a
+            })
+            .sum()
+    }
+}
+
+impl Add for ColorQuadric {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        let mut ret = self;
+        ret += rhs;
+        ret
+    }
+}
+
+impl AddAssign for ColorQuadric {
+    fn add_assign(&mut self, rhs: Self) {
+        for k in 0..3 {
+            self.q[k] += rhs.q[k];
+            for i in 0..4 {
+                self.b[k][i] += rhs.b[k][i];
+            }
+        }
+        self.a += rhs.a;
+    }
+}
+
+impl Sub for ColorQuadric {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        self + rhs * -1.0
+    }
+}
+
+impl SubAssign for ColorQuadric {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self += rhs * -1.0;
+    }
+}
+
+impl Mul<Flt> for ColorQuadric {
+    type Output = Self;
+    fn mul(self, rhs: Flt) -> Self {
+        let mut ret = self;
+        for k in 0..3 {
+            ret.q[k] = ret.q[k] * rhs;
+            ret.b[k].iter_mut().for_each(|x| *x *= rhs);
+        }
+        ret.a *= rhs;
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mesh, SimplifyOptions, EPS};
+
+    #[test]
+    fn color_quadric() {
+        let p = [Vct::new(0.0, 0.0, 0.0), Vct::new(1.0, 0.0, 0.0), Vct::new(0.0, 1.0, 0.0)];
+        let c = [Vct::new(0.0, 0.0, 0.0), Vct::new(1.0, 0.0, 0.5), Vct::new(0.0, 1.0, 0.5)];
+        let q = ColorQuadric::new(p, c);
+        for i in 0..3 {
+            assert!(q.error(p[i], c[i]).abs() < EPS);
+            assert!((q.optimal(p[i]).unwrap() - c[i]).len() < EPS);
+        }
+        let v = Vct::new(0.5, 0.25, 3.0);
+        assert!((q.optimal(v).unwrap() - Vct::new(0.5, 0.25, 0.375)).len() < EPS);
+        assert!((q.error(v, Vct::zero()) - (0.25 + 0.0625 + 0.140625)).abs() < EPS);
+    }
+
+    #[test]
+    fn simplify_colors() {
+        let mut mesh = Mesh::new("assets/block.obj");
+        mesh.color =
+            mesh.pos.iter().map(|p| if p.x > 0.0 { Vct::one() } else { Vct::zero() }).collect();
+        let opts = SimplifyOptions { ratio: 0.1, ..Default::default() };
+        let out = mesh.simplify_with(&opts);
+        assert_eq!(out.color.len(), out.pos.len());
+        // 颜色突变处的边会被保留，离交界较远的顶点颜色不变
+        out.pos.iter().zip(out.color.iter()).filter(|(p, _)| p.x.abs() > 2.0).for_each(|(p, c)| {
+            assert!((*c - if p.x > 0.0 { Vct::one() } else { Vct::zero() }).len() < 0.1);
+        });
+        let path = std::env::temp_dir().join("mesh_simplification_color.obj");
+        out.save(path.to_str().unwrap());
+        let back = Mesh::new(path.to_str().unwrap());
+        assert_eq!(back.color.len(), out.color.len());
+        let path = std::env::temp_dir().join("mesh_simplification_color.ply");
+        out.save(path.to_str().unwrap());
+        let back = Mesh::new(path.to_str().unwrap());
+        back.color.iter().zip(out.color.iter()).for_each(|(a, b)| assert!((*a - *b).len() < 0.01));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::mesh::Tri;
+use crate::quadric::Quadric;
+use crate::simplify::{PlacementPolicy, Strategy};
+use crate::{Aabb, Flt, Mat, Vct, PI};
+use std::cell::OnceCell;
+
+// 判断是否在邻域包围盒内时允许的相对误差，只用来容忍舍入
+const BOX_EPS: Flt = 1e-6;
+
+// 计算边 (v1, v2) 的收缩代价与新位置时可用的局部信息
+pub struct CollapseContext<'a> {
+    pub v1: usize,
+    pub v2: usize,
+    pub pos: &'a [Vct],
+    // 两个端点的误差矩阵（含特征边约束）之和
+    pub quadric: Quadric,
+    // 周围的面只在第一次用到时收集
+    around: &'a dyn Fn() -> Vec<Tri>,
+    faces: OnceCell<Vec<Tri>>,
+}
+
+impl<'a> CollapseContext<'a> {
+    pub(crate) fn new(
+        v1: usize,
+        v2: usize,
+        pos: &'a [Vct],
+        quadric: Quadric,
+        around: &'a dyn Fn() -> Vec<Tri>,
+    ) -> Self {
+        Self { v1, v2, pos, quadric, around, faces: OnceCell::new() }
+    }
+
+    // v1、v2 周围的面，收缩后同时含 v1、v2 的面会被删除
+    pub fn faces(&self) -> &[Tri] {
+        self.faces.get_or_init(self.around)
+    }
+
+    pub fn mid(&self) -> Vct {
+        (self.pos[self.v1] + self.pos[self.v2]) * 0.5
+    }
+
+    pub fn optimal(&self) -> Vct {
+        self.quadric.optimal_near(self.mid())
+    }
+
+    // v1、v2 周围所有顶点的包围盒
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(self.faces().iter().flat_map(|&(a, b, c)| [a, b, c]).map(|x| self.pos[x]))
+    }
+
+    // 近乎平面的邻域中解出的点可能远离这条边：不在邻域包围盒之内时不采用，改取两个端点与中点中代价最小的
+    pub fn bounded(&self, v: Vct) -> Vct {
+        let (p1, p2) = (self.pos[self.v1], self.pos[self.v2]);
+        // 大多数解都在两个端点的包围盒内，不必收集周围的面
+        if Aabb::new(p1.min(p2), p1.max(p2)).contains(v) {
+            return v;
+        }
+        let b = self.bounds();
+        if Aabb::new(b.lo - b.size() * BOX_EPS, b.hi + b.size() * BOX_EPS).contains(v) {
+            return v;
+        }
+        [p1, p2, self.mid()]
+            .iter()
+            .map(|&x| (self.quadric.evaluate(x), x))
+            .fold((Flt::INFINITY, p1), |a, b| if b.0 < a.0 { b } else { a })
+            .1
+    }
+
+    // 把 v1、v2 移到 v 之后，未被删除的面的 (旧法向, 新法向)，法向未单位化
+    pub fn moved_normals(&self, v: Vct) -> Vec<(Vct, Vct)> {
+        let (v1, v2) = (self.v1, self.v2);
+        let at = |x: usize| if x == v1 || x == v2 { v } else { self.pos[x] };
+        let nrm = |a: Vct, b: Vct, c: Vct| (b - a) % (c - a);
+        self.faces()
+            .iter()
+            .filter(|&&(a, b, c)| [a, b, c].iter().filter(|&&x| x == v1 || x == v2).count() < 2)
+            .map(|&(a, b, c)| {
+                (nrm(self.pos[a], self.pos[b], self.pos[c]), nrm(at(a), at(b), at(c)))
+            })
+            .collect()
+    }
+
+    // 收缩后保持邻域体积不变，若边在边界上则再保持边界的面积向量不变
+    pub fn volume_constraints(&self) -> Vec<(Vct, Flt)> {
+        let (v1, v2, pos) = (self.v1, self.v2, self.pos);
+        let (mut gv, mut dv, mut dir) = (Vct::zero(), 0.0, vec![]);
+        self.faces().iter().for_each(|&(mut t0, mut t1, mut t2)| {
+            let has_v1 = t0 == v1 || t1 == v1 || t2 == v1;
+            while t0 != v1 && (t0 != v2 || has_v1) {
+                let t = t0;
+                t0 = t1;
+                t1 = t2;
+                t2 = t;
+            }
+            let n = (pos[t1] - pos[t0]) % (pos[t2] - pos[t0]);
+            gv += n;
+            dv += n.dot(pos[t0]);
+            dir.extend_from_slice(&[(t0, t1), (t1, t2), (t2, t0)]);
+        });
+        let (mut e1, mut e2) = (Vct::zero(), Vct::zero());
+        let end = |x| x == v1 || x == v2;
+        dir.iter().filter(|&&(a, b)| !dir.contains(&(b, a))).for_each(|&(a, b)| {
+            if end(a) || end(b) {
+                e2 += pos[a] % pos[b];
+            }
+            if end(a) && !end(b) {
+                e1 += pos[b];
+            } else if end(b) && !end(a) {
+                e1 -= pos[a];
+            }
+        });
+        let mut cons = vec![(gv, dv)];
+        if e1.len2() > 0.0 {
+            cons.push((Vct::new(0.0, e1.z, -e1.y), e2.x));
+            cons.push((Vct::new(-e1.z, 0.0, e1.x), e2.y));
+            cons.push((Vct::new(e1.y, -e1.x, 0.0), e2.z));
+        }
+        cons
+    }
+}
+
+// 收缩代价，越小越先收缩
+pub trait CollapseCost: Sync {
+    fn cost(&self, ctx: &CollapseContext, v: Vct) -> Flt;
+}
+
+// 光滑区域中新顶点的候选位置，最终取代价最小的一个；特征线上的顶点仍只沿特征线移动
+pub trait Placement: Sync {
+    fn candidates(&self, ctx: &CollapseContext) -> Vec<Vct>;
+}
+
+impl<F: Fn(&CollapseContext, Vct) -> Flt + Sync> CollapseCost for F {
+    fn cost(&self, ctx: &CollapseContext, v: Vct) -> Flt {
+        self(ctx, v)
+    }
+}
+
+impl<F: Fn(&CollapseContext) -> Vec<Vct> + Sync> Placement for F {
+    fn candidates(&self, ctx: &CollapseContext) -> Vec<Vct> {
+        self(ctx)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QuadricCost;
+
+impl CollapseCost for QuadricCost {
+    fn cost(&self, ctx: &CollapseContext, v: Vct) -> Flt {
+        ctx.quadric.evaluate(v)
+    }
+}
+
+// 与新位置无关，短边先收缩
+#[derive(Clone, Copy, Debug)]
+pub struct EdgeLengthCost;
+
+impl CollapseCost for EdgeLengthCost {
+    fn cost(&self, ctx: &CollapseContext, _: Vct) -> Flt {
+        (ctx.pos[ctx.v1] - ctx.pos[ctx.v2]).len2()
+    }
+}
+
+// 误差矩阵的代价加上周围各面法向变化的惩罚：每个面贡献 新面积 × (1 - cos 夹角) × 权重，
+// 两项量纲都是长度的平方，面翻转时惩罚最大
+#[derive(Clone, Copy, Debug)]
+pub struct NormalDeviationCost(pub Flt);
+
+impl CollapseCost for NormalDeviationCost {
+    fn cost(&self, ctx: &CollapseContext, v: Vct) -> Flt {
+        let penalty: Flt = ctx
+            .moved_normals(v)
+            .iter()
+            .filter(|(n0, n1)| n0.len2() > 0.0 && n1.len2() > 0.0)
+            .map(|(n0, n1)| (1.0 - n0.dot(*n1) / (n0.len() * n1.len())) * n1.len() * 0.5)
+            .sum();
+        ctx.quadric.evaluate(v) + penalty * self.0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QuadricPlacement {
+    pub strategy: Strategy,
+    pub policy: PlacementPolicy,
+}
+
+impl Placement for QuadricPlacement {
+    fn candidates(&self, ctx: &CollapseContext) -> Vec<Vct> {
+        let optimal = ctx.bounded(match self.strategy {
+            Strategy::Quadric => ctx.optimal(),
+            Strategy::Volume => {
+                solve_constrained(&ctx.quadric, &ctx.volume_constraints(), ctx.mid())
+            },
+        });
+        let (p1, p2) = (ctx.pos[ctx.v1], ctx.pos[ctx.v2]);
+        match self.policy {
+            PlacementPolicy::Optimal => vec![optimal],
+            PlacementPolicy::Endpoint => vec![p1, p2],
+            PlacementPolicy::Best => vec![p1, p2, ctx.mid(), optimal],
+        }
+    }
+}
+
+// 在线性约束 g·x = d 下最小化 qv 的误差，与之前的约束近似线性相关的约束被舍弃
+fn solve_constrained(qv: &Quadric, cons: &[(Vct, Flt)], mid: Vct) -> Vct {
+    let alpha = (PI / 180.0).sin();
+    let mut u: Vec<(Vct, Flt)> = vec![];
+    for &(g, d) in cons {
+        let (mut g2, mut d2) = (g, d);
+        u.iter().for_each(|&(uj, ej)| {
+            let t = g2.dot(uj);
+            g2 -= uj * t;
+            d2 -= ej * t;
+        });
+        let len = g2.len();
+        if u.len() < 3 && len > 0.0 && len > alpha * g.len() {
+            u.push((g2 / len, d2 / len));
+        }
+    }
+    let x0 = u.iter().fold(Vct::zero(), |x, &(uj, ej)| x + uj * ej);
+    let basis = match u.len() {
+        0 => vec![Vct::new(1.0, 0.0, 0.0), Vct::new(0.0, 1.0, 0.0), Vct::new(0.0, 0.0, 1.0)],
+        1 => {
+            let n = u[0].0;
+            let a = if n.x.abs() < 0.5 { Vct::new(1.0, 0.0, 0.0) } else { Vct::new(0.0, 1.0, 0.0) };
+            let n1 = (n % a).norm();
+            vec![n1, n % n1]
+        },
+        2 => vec![(u[0].0 % u[1].0).norm()],
+        _ => return x0,
+    };
+    let av = |v: Vct| qv.mul_vct3(v);
+    let r = av(x0) + qv.linear();
+    let mut m = Mat::identity();
+    let mut rhs = [0.0; 3];
+    for i in 0..basis.len() {
+        for j in 0..basis.len() {
+            m[i][j] = basis[i].dot(av(basis[j]));
+        }
+        rhs[i] = -basis[i].dot(r);
+    }
+    let y: Vec<_> = match m.inverse() {
+        Some(inv) => (0..basis.len())
+            .map(|i| (0..basis.len()).map(|j| inv[i][j] * rhs[j]).sum::<Flt>())
+            .collect(),
+        None => basis.iter().map(|b| b.dot(mid - x0)).collect(),
+    };
+    basis.iter().zip(y.iter()).fold(x0, |x, (&b, &y)| x + b * y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mesh, SimplifyOptions};
+
+    #[test]
+    fn bounded() {
+        let pos = [
+            Vct::new(0.0, 0.0, 0.0),
+            Vct::new(1.0, 0.0, 0.0),
+            Vct::new(0.0, 1.0, 0.0),
+            Vct::new(1.0, 1.0, 0.2),
+        ];
+        let around = || vec![(0, 1, 2), (1, 3, 2)];
+        let quadric = Quadric::plane(Vct::new(0.0, 0.0, 1.0), Vct::zero());
+        let ctx = CollapseContext::new(1, 2, &pos, quadric, &around);
+        assert_eq!(ctx.bounds(), Aabb::new(Vct::zero(), Vct::new(1.0, 1.0, 0.2)));
+        let inside = Vct::new(0.5, 0.6, 0.15);
+        assert_eq!(ctx.bounded(inside), inside);
+        // 邻域包围盒之外的点，即使只在外面一点，也被换成代价最小的端点或中点
+        assert_eq!(ctx.bounded(Vct::new(0.5, 0.6, 0.25)), pos[1]);
+        assert_eq!(ctx.bounded(Vct::new(40.0, -3.0, 0.0)), pos[1]);
+    }
+
+    #[test]
+    fn custom_cost() {
+        let mesh = Mesh::new("assets/block.obj");
+        let opts = SimplifyOptions { ratio: 0.2, ..Default::default() };
+        let placement =
+            QuadricPlacement { strategy: Strategy::Quadric, policy: PlacementPolicy::Optimal };
+        let out = mesh.simplify_custom(&opts, &QuadricCost, &placement);
+        assert_eq!(out.pos, mesh.simplify_with(&opts).pos);
+        for out in [
+            mesh.simplify_custom(&opts, &EdgeLengthCost, &placement),
+            mesh.simplify_custom(&opts, &NormalDeviationCost(1.0), &placement),
+            mesh.simplify_custom(
+                &opts,
+                &|ctx: &CollapseContext, v| ctx.quadric.evaluate(v),
+                &|ctx: &CollapseContext| vec![ctx.mid()],
+            ),
+        ] {
+            assert!(out.tri.len() < mesh.tri.len() / 4);
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use crate::float::Float;
+use crate::mesh::Tri;
+use crate::{Flt, Mesh, Vct};
+use std::collections::HashMap;
+
+pub const NONE: usize = usize::MAX;
+
+// 第 f 个面的三条半边是 3f、3f+1、3f+2，第 k 条从第 k 个角指向第 k+1 个角
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HalfEdge {
+    pub vert: usize,
+    pub twin: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct HalfEdgeMesh<T = Flt> {
+    pub pos: Vec<Vct<T>>,
+    pub he: Vec<HalfEdge>,
+    // 顶点的一条出边，边界上的顶点取其唯一的边界出边
+    pub out: Vec<usize>,
+    pub dele_v: Vec<bool>,
+    pub dele_f: Vec<bool>,
+}
+
+impl<T: Float> HalfEdgeMesh<T> {
+    // 非流形的网格返回 None
+    pub fn new(mesh: &Mesh<T>) -> Option<Self> {
+        let n = mesh.pos.len();
+        let mut ret = Self {
+            pos: mesh.pos.clone(),
+            he: vec![HalfEdge { vert: NONE, twin: NONE }; mesh.tri.len() * 3],
+            out: vec![NONE; n],
+            dele_v: vec![false; n],
+            dele_f: vec![false; mesh.tri.len()],
+        };
+        let mut dir = HashMap::new();
+        for (f, &(a, b, c)) in mesh.tri.iter().enumerate() {
+            if a == b || b == c || c == a {
+                return None;
+            }
+            ret.set_face(f, (a, b, c));
+            for k in 0..3 {
+                let h = f * 3 + k;
+                if dir.insert((ret.origin(h), ret.target(h)), h).is_some() {
+                    return None;
+                }
+            }
+        }
+        for h in 0..ret.he.len() {
+            if let Some(&t) = dir.get(&(ret.target(h), ret.origin(h))) {
+                ret.he[h].twin = t;
+            }
+        }
+        let faces: Vec<_> = (0..mesh.tri.len()).collect();
+        ret.refresh(&faces);
+        // 每个顶点相邻的面必须构成一个扇形
+        let mut deg = vec![0; n];
+        (0..ret.he.len()).for_each(|h| deg[ret.origin(h)] += 1);
+        for (v, &d) in deg.iter().enumerate() {
+            if d == 0 {
+                ret.dele_v[v] = true;
+            } else if ret.outgoing(v).len() != d {
+                return None;
+            }
+        }
+        Some(ret)
+    }
+
+    pub fn to_mesh(&self) -> Mesh<T> {
+        let mut id = vec![NONE; self.pos.len()];
+        let mut pos = vec![];
+        let mut tri = vec![];
+        for f in (0..self.dele_f.len()).filter(|&f| !self.dele_f[f]) {
+            let (a, b, c) = self.face(f);
+            let mut g = |v: usize| {
+                if id[v] == NONE {
+                    id[v] = pos.len();
+                    pos.push(self.pos[v]);
+                }
+                id[v]
+            };
+            tri.push((g(a), g(b), g(c)));
+        }
+        Mesh::from_raw(pos, tri)
+    }
+
+    pub fn next(&self, h: usize) -> usize {
+        h - h % 3 + (h + 1) % 3
+    }
+
+    pub fn prev(&self, h: usize) -> usize {
+        h - h % 3 + (h + 2) % 3
+    }
+
+    pub fn twin(&self, h: usize) -> Option<usize> {
+        Some(self.he[h].twin).filter(|&t| t != NONE)
+    }
+
+    pub fn target(&self, h: usize) -> usize {
+        self.he[h].vert
+    }
+
+    pub fn origin(&self, h: usize) -> usize {
+        self.he[self.prev(h)].vert
+    }
+
+    pub fn face_of(&self, h: usize) -> usize {
+        h / 3
+    }
+
+    pub fn face(&self, f: usize) -> Tri {
+        (self.origin(f * 3), self.origin(f * 3 + 1), self.origin(f * 3 + 2))
+    }
+
+    pub fn is_boundary_edge(&self, h: usize) -> bool {
+        self.twin(h).is_none()
+    }
+
+    pub fn is_boundary_vertex(&self, v: usize) -> bool {
+        self.out[v] != NONE && self.is_boundary_edge(self.out[v])
+    }
+
+    // 边两侧的面，边界边只有一个
+    pub fn edge_faces(&self, h: usize) -> (usize, Option<usize>) {
+        (self.face_of(h), self.twin(h).map(|t| self.face_of(t)))
+    }
+
+    pub fn find_edge(&self, a: usize, b: usize) -> Option<usize> {
+        self.outgoing(a).into_iter().find(|&h| self.target(h) == b)
+    }
+
+    // 从 out[v] 开始绕顶点旋转得到所有出边
+    pub fn outgoing(&self, v: usize) -> Vec<usize> {
+        let mut ret = vec![];
+        let mut h = self.out[v];
+        while h != NONE {
+            ret.push(h);
+            h = match self.twin(self.prev(h)) {
+                Some(t) if t != self.out[v] => t,
+                _ => NONE,
+            };
+        }
+        ret
+    }
+
+    pub fn vertex_faces(&self, v: usize) -> Vec<usize> {
+        self.outgoing(v).into_iter().map(|h| self.face_of(h)).collect()
+    }
+
+    pub fn one_ring(&self, v: usize) -> Vec<usize> {
+        let out = self.outgoing(v);
+        let mut ret: Vec<_> = out.iter().map(|&h| self.target(h)).collect();
+        if let Some(&h) = out.last() {
+            if self.is_boundary_edge(self.prev(h)) {
+                ret.push(self.origin(self.prev(h)));
+            }
+        }
+        ret
+    }
+
+    pub fn valence(&self, v: usize) -> usize {
+        self.one_ring(v).len()
+    }
+
+    // 每个边界环按顺序给出顶点
+    pub fn boundary_loops(&self) -> Vec<Vec<usize>> {
+        let mut vis = vec![false; self.he.len()];
+        let mut ret = vec![];
+        for s in 0..self.he.len() {
+            if self.dele_f[self.face_of(s)] || vis[s] || !self.is_boundary_edge(s) {
+                continue;
+            }
+            let mut lp = vec![];
+            let mut h = s;
+            while !vis[h] {
+                vis[h] = true;
+                lp.push(self.origin(h));
+                h = self.out[self.target(h)];
+            }
+            ret.push(lp);
+        }
+        ret
+    }
+
+    fn set_face(&mut self, f: usize, (a, b, c): Tri) {
+        self.he[f * 3].vert = b;
+        self.he[f * 3 + 1].vert = c;
+        self.he[f * 3 + 2].vert = a;
+    }
+
+    fn link(&mut self, a: usize, b: usize) {
+        if a != NONE {
+            self.he[a].twin = b;
+        }
+        if b != NONE {
+            self.he[b].twin = a;
+        }
+    }
+
+    // 重新连接 faces 内的半边与 faces 内部或 ext 中的反向半边
+    fn relink(&mut self, faces: &[usize], ext: &[usize]) {
+        let mut dir = HashMap::new();
+        let hs: Vec<_> = faces.iter().flat_map(|&f| vec![f * 3, f * 3 + 1, f * 3 + 2]).collect();
+        hs.iter().chain(ext.iter()).filter(|&&h| h != NONE).for_each(|&h| {
+            dir.insert((self.origin(h), self.target(h)), h);
+        });
+        for &h in &hs {
+            let t = *dir.get(&(self.target(h), self.origin(h))).unwrap_or(&NONE);
+            self.he[h].twin = NONE;
+            self.link(h, t);
+        }
+    }
+
+    // 更新 faces 中所有顶点的出边，使边界顶点的出边为边界边
+    fn refresh(&mut self, faces: &[usize]) {
+        let mut vs = vec![];
+        for &f in faces {
+            if self.dele_f[f] {
+                continue;
+            }
+            for h in f * 3..f * 3 + 3 {
+                let v = self.origin(h);
+                self.out[v] = h;
+                vs.push(v);
+            }
+        }
+        for v in vs {
+            let s = self.out[v];
+            let mut h = s;
+            while let Some(t) = self.twin(h) {
+                h = self.next(t);
+                if h == s {
+                    break;
+                }
+            }
+            self.out[v] = h;
+        }
+    }
+
+    fn add_face(&mut self, t: Tri) -> usize {
+        self.he.extend_from_slice(&[HalfEdge { vert: NONE, twin: NONE }; 3]);
+        self.dele_f.push(false);
+        let f = self.dele_f.len() - 1;
+        self.set_face(f, t);
+        f
+    }
+
+    // 收缩 h 的两个端点到 p，保留 h 的终点，返回保留的顶点；不满足连接条件时不做任何修改
+    pub fn collapse(&mut self, h: usize, p: Vct<T>) -> Option<usize> {
+        self.collapse_to(h, self.target(h), p)
+    }
+
+    // 同 collapse，但保留 h 的端点 keep
+    pub fn collapse_to(&mut self, h: usize, keep: usize, p: Vct<T>) -> Option<usize> {
+        let (a, b) = match keep == self.target(h) {
+            true => (self.origin(h), keep),
+            false => (self.target(h), self.origin(h)),
+        };
+        let mut opp = vec![self.target(self.next(h))];
+        let (ra, rb) = (self.one_ring(a), self.one_ring(b));
+        match self.twin(h) {
+            Some(t) => {
+                opp.push(self.target(self.next(t)));
+                if self.is_boundary_vertex(a) && self.is_boundary_vertex(b) {
+                    return None;
+                }
+                if ra.len() + rb.len() <= 6 {
+                    return None;
+                }
+            },
+            None if ra.len() + rb.len() <= 4 => return None,
+            None => (),
+        }
+        if rb.iter().filter(|v| ra.contains(v)).count() != opp.len() {
+            return None;
+        }
+        let out_a = self.outgoing(a);
+        let mut keep = vec![];
+        for &e in [Some(h), self.twin(h)].iter().flatten() {
+            let (tn, tp) = (self.he[self.next(e)].twin, self.he[self.prev(e)].twin);
+            self.link(tn, tp);
+            keep.extend([tn, tp].iter().filter(|&&t| t != NONE).map(|&t| self.face_of(t)));
+            let f = self.face_of(e);
+            self.dele_f[f] = true;
+        }
+        for &o in &out_a {
+            let i = self.prev(o);
+            self.he[i].vert = b;
+            keep.push(self.face_of(o));
+        }
+        self.dele_v[a] = true;
+        self.out[a] = NONE;
+        self.pos[b] = p;
+        opp.iter().chain([b].iter()).for_each(|&v| self.out[v] = NONE);
+        keep.retain(|&f| !self.dele_f[f]);
+        self.refresh(&keep);
+        Some(b)
+    }
+
+    // 在 h 上插入位于 p 的新顶点，相邻的面一分为二，返回新顶点
+    pub fn split(&mut self, h: usize, p: Vct<T>) -> usize {
+        let (a, b, c) = (self.origin(h), self.target(h), self.target(self.next(h)));
+        let f0 = self.face_of(h);
+        let mut ext = vec![self.he[self.next(h)].twin, self.he[self.prev(h)].twin];
+        self.pos.push(p);
+        self.out.push(NONE);
+        self.dele_v.push(false);
+        let m = self.pos.len() - 1;
+        let mut faces = vec![f0];
+        self.set_face(f0, (a, m, c));
+        faces.push(self.add_face((m, b, c)));
+        if let Some(t) = self.twin(h) {
+            let d = self.target(self.next(t));
+            let f1 = self.face_of(t);
+            ext.push(self.he[self.next(t)].twin);
+            ext.push(self.he[self.prev(t)].twin);
+            self.set_face(f1, (b, m, d));
+            faces.push(f1);
+            faces.push(self.add_face((m, a, d)));
+        }
+        self.relink(&faces, &ext);
+        self.refresh(&faces);
+        m
+    }
+
+    // 翻转内部边 h，若为边界边或翻转后的边已存在则返回 false
+    pub fn flip(&mut self, h: usize) -> bool {
+        let t = match self.twin(h) {
+            Some(t) => t,
+            None => return false,
+        };
+        let (a, b) = (self.origin(h), self.target(h));
+        let (c, d) = (self.target(self.next(h)), self.target(self.next(t)));
+        if c == d || self.find_edge(c, d).is_some() {
+            return false;
+        }
+        let ext: Vec<_> = [self.next(h), self.prev(h), self.next(t), self.prev(t)]
+            .iter()
+            .map(|&e| self.he[e].twin)
+            .collect();
+        let faces = [self.face_of(h), self.face_of(t)];
+        self.set_face(faces[0], (a, d, c));
+        self.set_face(faces[1], (b, c, d));
+        self.relink(&faces, &ext);
+        self.refresh(&faces);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn euler(m: &HalfEdgeMesh) -> i64 {
+        let v = m.dele_v.iter().filter(|&&d| !d).count() as i64;
+        let f = m.dele_f.iter().filter(|&&d| !d).count() as i64;
+        let e = (0..m.he.len())
+            .filter(|&h| !m.dele_f[h / 3] && (m.twin(h).is_none() || m.twin(h).unwrap() > h))
+            .count() as i64;
+        v - e + f
+    }
+
+    #[test]
+    fn traversal() {
+        let m = HalfEdgeMesh::new(&Mesh::new("assets/cube.obj")).unwrap();
+        assert_eq!(euler(&m), 2);
+        assert!(m.boundary_loops().is_empty());
+        let deg: usize = (0..8).map(|v| m.valence(v)).sum();
+        assert_eq!(deg, 36);
+        (0..8).for_each(|v| assert_eq!(m.vertex_faces(v).len(), m.valence(v)));
+
+        let mut open = Mesh::new("assets/cube.obj");
+        open.tri.truncate(10);
+        let m = HalfEdgeMesh::new(&open).unwrap();
+        let loops = m.boundary_loops();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].len(), 4);
+        loops[0].iter().for_each(|&v| assert!(m.is_boundary_vertex(v)));
+        // 边界边只有一个方向，collapse_to 可以保留它的起点
+        let mut m = m;
+        let h = (0..m.he.len()).find(|&h| m.is_boundary_edge(h)).unwrap();
+        let (a, b) = (m.origin(h), m.target(h));
+        assert_eq!(m.collapse_to(h, a, m.pos[a]), Some(a));
+        assert!(m.dele_v[b] && !m.dele_v[a] && m.is_boundary_vertex(a));
+        assert_eq!(euler(&m), 1);
+
+        let m = HalfEdgeMesh::new(&Mesh::<f32>::load("assets/cube.obj")).unwrap();
+        assert_eq!(m.to_mesh().pos.len(), 8);
+    }
+
+    #[test]
+    fn non_manifold() {
+        let mut mesh = Mesh::new("assets/cube.obj");
+        let t = mesh.tri[0];
+        mesh.tri.push(t);
+        assert!(HalfEdgeMesh::new(&mesh).is_none());
+    }
+
+    #[test]
+    fn operators() {
+        let mut m = HalfEdgeMesh::new(&Mesh::new("assets/sphere.obj")).unwrap();
+        let v = m.split(0, Vct::zero());
+        assert_eq!(m.valence(v), 4);
+        assert_eq!(euler(&m), 2);
+        let h = m.out[v];
+        assert!(m.flip(h) || m.twin(h).is_none());
+        assert_eq!(euler(&m), 2);
+        let mut cnt = 0;
+        for h in 0..m.he.len() {
+            if !m.dele_f[h / 3] && m.collapse(h, m.pos[m.target(h)]).is_some() {
+                cnt += 1;
+            }
+        }
+        assert!(cnt > 1000);
+        assert_eq!(euler(&m), 2);
+        let mesh = m.to_mesh();
+        let m = HalfEdgeMesh::new(&mesh).unwrap();
+        assert_eq!(euler(&m), 2);
+    }
+}
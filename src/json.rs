@@ -0,0 +1,357 @@
+use crate::Flt;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Num(Flt),
+    Str(String),
+    Arr(Vec<Json>),
+    // 保持字段的插入顺序
+    Obj(Vec<(String, Json)>),
+}
+
+fn escape(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret += "\\\"",
+            '\\' => ret += "\\\\",
+            '\n' => ret += "\\n",
+            '\r' => ret += "\\r",
+            '\t' => ret += "\\t",
+            c if (c as u32) < 0x20 => ret += &format!("\\u{:04x}", c as u32),
+            c => ret.push(c),
+        }
+    }
+    ret + "\""
+}
+
+// 递归下降解析，错误信息带有行号与列号
+struct Parser<'a> {
+    s: &'a [u8],
+    i: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &str) -> String {
+        let line = self.s[..self.i].iter().filter(|&&c| c == b'\n').count() + 1;
+        let col =
+            self.i - self.s[..self.i].iter().rposition(|&c| c == b'\n').map_or(0, |p| p + 1) + 1;
+        format!("line {} column {}: {}", line, col, msg)
+    }
+
+    fn skip(&mut self) {
+        while self.i < self.s.len() && self.s[self.i].is_ascii_whitespace() {
+            self.i += 1;
+        }
+    }
+
+    fn eat(&mut self, c: u8) -> Result<(), String> {
+        self.skip();
+        match self.s.get(self.i) {
+            Some(&x) if x == c => {
+                self.i += 1;
+                Ok(())
+            },
+            _ => Err(self.error(&format!("expected `{}`", c as char))),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, v: Json) -> Result<Json, String> {
+        match self.s[self.i..].starts_with(word.as_bytes()) {
+            true => {
+                self.i += word.len();
+                Ok(v)
+            },
+            false => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip();
+        match self.s.get(self.i) {
+            None => Err(self.error("unexpected end of input")),
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::Str),
+            Some(b'[') => {
+                self.i += 1;
+                let mut a = vec![];
+                self.skip();
+                if self.s.get(self.i) == Some(&b']') {
+                    self.i += 1;
+                    return Ok(Json::Arr(a));
+                }
+                loop {
+                    a.push(self.value()?);
+                    self.skip();
+                    match self.s.get(self.i) {
+                        Some(b',') => self.i += 1,
+                        _ => break,
+                    }
+                }
+                self.eat(b']')?;
+                Ok(Json::Arr(a))
+            },
+            Some(b'{') => {
+                self.i += 1;
+                let mut o: Vec<(String, Json)> = vec![];
+                self.skip();
+                if self.s.get(self.i) == Some(&b'}') {
+                    self.i += 1;
+                    return Ok(Json::Obj(o));
+                }
+                loop {
+                    self.skip();
+                    if self.s.get(self.i) != Some(&b'"') {
+                        return Err(self.error("expected a string key"));
+                    }
+                    let at = self.i;
+                    let k = self.string()?;
+                    if o.iter().any(|(x, _)| *x == k) {
+                        self.i = at;
+                        return Err(self.error(&format!("duplicate key `{}`", k)));
+                    }
+                    self.eat(b':')?;
+                    o.push((k, self.value()?));
+                    self.skip();
+                    match self.s.get(self.i) {
+                        Some(b',') => self.i += 1,
+                        _ => break,
+                    }
+                }
+                self.eat(b'}')?;
+                Ok(Json::Obj(o))
+            },
+            Some(_) => {
+                let start = self.i;
+                while self.i < self.s.len() && b"+-.eE0123456789".contains(&self.s[self.i]) {
+                    self.i += 1;
+                }
+                let t = std::str::from_utf8(&self.s[start..self.i]).unwrap();
+                match t.parse::<Flt>() {
+                    Ok(x) if !t.is_empty() => Ok(Json::Num(x)),
+                    _ => {
+                        self.i = start;
+                        Err(self.error("unexpected character"))
+                    },
+                }
+            },
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.i += 1;
+        let mut buf = vec![];
+        loop {
+            match self.s.get(self.i) {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    self.i += 1;
+                    let c = match self.s.get(self.i) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let hex = self.s.get(self.i + 1..self.i + 5).and_then(|h| {
+                                u32::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok()
+                            });
+                            self.i += 4;
+                            hex.and_then(char::from_u32).unwrap_or('\u{fffd}')
+                        },
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                },
+                Some(&c) => buf.push(c),
+            }
+            self.i += 1;
+        }
+        self.i += 1;
+        String::from_utf8(buf).map_err(|_| self.error("invalid UTF-8"))
+    }
+}
+
+impl Json {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut p = Parser { s: s.as_bytes(), i: 0 };
+        let v = p.value()?;
+        p.skip();
+        match p.i == p.s.len() {
+            true => Ok(v),
+            false => Err(p.error("trailing characters")),
+        }
+    }
+
+    pub fn as_num(&self) -> Option<Flt> {
+        match self {
+            Json::Num(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Obj(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    // 每层缩进两个空格
+    pub fn pretty(&self) -> String {
+        let mut s = String::new();
+        self.write(&mut s, Some(0));
+        s
+    }
+
+    fn write(&self, s: &mut String, indent: Option<usize>) {
+        let (open, close) = match indent {
+            Some(k) => (format!("\n{}", "  ".repeat(k + 1)), format!("\n{}", "  ".repeat(k))),
+            None => (String::new(), String::new()),
+        };
+        let inner = indent.map(|k| k + 1);
+        match self {
+            Json::Null => *s += "null",
+            Json::Bool(b) => *s += &b.to_string(),
+            Json::Num(x) if x.is_finite() => *s += &x.to_string(),
+            Json::Num(_) => *s += "null",
+            Json::Str(x) => *s += &escape(x),
+            Json::Arr(a) if a.is_empty() => *s += "[]",
+            Json::Obj(o) if o.is_empty() => *s += "{}",
+            Json::Arr(a) => {
+                *s += "[";
+                a.iter().enumerate().for_each(|(i, v)| {
+                    *s += if i > 0 { "," } else { "" };
+                    *s += &open;
+                    v.write(s, inner);
+                });
+                *s += &close;
+                *s += "]";
+            },
+            Json::Obj(o) => {
+                *s += "{";
+                o.iter().enumerate().for_each(|(i, (k, v))| {
+                    *s += if i > 0 { "," } else { "" };
+                    *s += &open;
+                    *s += &escape(k);
+                    *s += if indent.is_some() { ": " } else { ":" };
+                    v.write(s, inner);
+                });
+                *s += &close;
+                *s += "}";
+            },
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = String::new();
+        self.write(&mut s, None);
+        write!(f, "{}", s)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+impl From<Flt> for Json {
+    fn from(x: Flt) -> Self {
+        Json::Num(x)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(x: usize) -> Self {
+        Json::Num(x as Flt)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(x: &str) -> Self {
+        Json::Str(x.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(x: String) -> Self {
+        Json::Str(x)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(x: Option<T>) -> Self {
+        x.map_or(Json::Null, |x| x.into())
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(x: Vec<T>) -> Self {
+        Json::Arr(x.into_iter().map(|x| x.into()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write() {
+        let j = Json::Obj(vec![
+            ("name".to_string(), "a\"b\\c\n".into()),
+            ("n".to_string(), 3usize.into()),
+            ("x".to_string(), Json::from(vec![0.5, Flt::NAN])),
+            ("e".to_string(), Json::from(None::<bool>)),
+            ("o".to_string(), Json::Obj(vec![])),
+        ]);
+        assert_eq!(j.to_string(), r#"{"name":"a\"b\\c\n","n":3,"x":[0.5,null],"e":null,"o":{}}"#);
+        assert_eq!(Json::from(vec![true]).pretty(), "[\n  true\n]");
+        assert_eq!(j.get("n"), Some(&Json::Num(3.0)));
+    }
+
+    #[test]
+    fn parse() {
+        let s = r#" {"a": [1, -2.5e1, true, null], "b": {"c": "x\"\u00e9\n"}, "d": []} "#;
+        let j = Json::parse(s).unwrap();
+        assert_eq!(
+            j.get("a"),
+            Some(&Json::from(vec![Json::Num(1.0), Json::Num(-25.0), true.into(), Json::Null]))
+        );
+        assert_eq!(j.get("b").and_then(|b| b.get("c")).and_then(|c| c.as_str()), Some("x\"é\n"));
+        assert_eq!(Json::parse(&j.to_string()), Ok(j.clone()));
+        assert_eq!(Json::parse(&j.pretty()), Ok(j));
+        assert_eq!(
+            Json::parse("{\n  \"a\": 1,\n  \"a\": 2}"),
+            Err("line 3 column 3: duplicate key `a`".to_string())
+        );
+        assert!(Json::parse("[1, 2").unwrap_err().contains("expected `]`"));
+        assert!(Json::parse("{\"a\": tru}").is_err());
+        assert!(Json::parse("1 2").is_err());
+    }
+}
@@ -1,9 +1,43 @@
+pub mod aabb;
+pub mod batch;
+pub mod bvh;
+pub mod cluster;
+pub mod color;
+pub mod cost;
+pub mod float;
+pub mod halfedge;
+pub mod json;
 pub mod mat;
 pub mod mesh;
+pub mod normal;
+pub mod ooc;
+pub mod parallel;
+pub mod preset;
+pub mod progress;
+pub mod quadric;
+pub mod repair;
+pub mod simplify;
+pub mod skin;
+pub mod stats;
 pub mod vct;
 
+pub use aabb::Aabb;
+pub use bvh::{Bvh, Nearest, RayHit};
+pub use cluster::ClusterSize;
+pub use cost::{CollapseContext, CollapseCost, Placement};
+pub use float::Float;
+pub use halfedge::HalfEdgeMesh;
+pub use json::Json;
 pub use mat::Mat;
-pub use mesh::Mesh;
+pub use mesh::{Format, Mesh, VertexSource};
+pub use normal::NormalWeight;
+pub use ooc::simplify_out_of_core;
+pub use progress::{Control, Observer, Progress};
+pub use quadric::Quadric;
+pub use repair::RepairReport;
+pub use simplify::{Collapse, PlacementPolicy, Simplified, SimplifyOptions, Strategy};
+pub use skin::Skin;
+pub use stats::{Distance, EdgeLengths, Histogram, MeshStats, TriangleQuality};
 pub use vct::Vct;
 pub type Flt = f64;
 
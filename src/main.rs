@@ -1,16 +1,724 @@
 extern crate mesh_simplification;
 
-use mesh_simplification::{Flt, Mesh};
+use mesh_simplification::batch::{collect_inputs, report_csv, report_json, simplify_batch};
+use mesh_simplification::{
+    simplify_out_of_core, ClusterSize, Control, Flt, Format, Mesh, NormalWeight, Observer,
+    Progress, SimplifyOptions, VertexSource,
+};
 
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::fs;
+use std::panic;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const BIN: &str = "mesh_simplification";
+
+// 退出码：Usage 为 2，Run 为 1；库中的 panic 也按 Run 处理
+#[derive(Debug, PartialEq)]
+enum Error {
+    Usage(String),
+    Run(String),
+}
+
+// (选项, 参数名, 说明)，参数名为空的是开关
+type Opt = (&'static str, &'static str, &'static str);
+
+struct Command {
+    name: &'static str,
+    args: &'static str,
+    // 位置参数个数的范围
+    nargs: (usize, usize),
+    about: &'static str,
+    opts: &'static [&'static [Opt]],
+}
+
+const PRESET_OPTS: &[Opt] = &[
+    ("--preset", "NAME", "start from a preset in the presets file, or from a .json file"),
+    ("--presets", "FILE", "file of named presets (default presets.json)"),
+    ("--save-preset", "FILE", "write the effective options to FILE as JSON"),
+];
+
+const QEM_OPTS: &[Opt] = &[
+    ("--max-error", "E", "stop before the first collapse costing more than E"),
+    ("--boundary", "MODE", "free | preserve | lock (default free)"),
+    ("--feature-angle", "DEG", "keep edges whose dihedral angle exceeds DEG"),
+    ("--feature-weight", "W", "weight of the feature edge constraints (default 100)"),
+    ("--placement", "P", "optimal | endpoint | best (default optimal)"),
+    ("--strategy", "S", "quadric | volume (default quadric)"),
+    ("--quadric-weight", "W", "uniform | area | angle face quadrics (default uniform)"),
+    (
+        "--color-weight",
+        "W",
+        "cost of a unit color difference, relative to the diagonal (default 0.01)",
+    ),
+    (
+        "--skin-penalty",
+        "W",
+        "extra cost of collapsing vertices with different main bones (default 0)",
+    ),
+    ("--normalize", "", "scale the mesh into a unit bounding box, so errors are relative"),
+    ("--threads", "N", "number of threads per mesh (default 1)"),
+];
+
+const VERTEX_OPTS: &[Opt] = &[
+    ("--weights", "FILE", "per-vertex weights and locks, one `index weight|lock` per line"),
+    ("--creases", "FILE", "crease edges to keep, one `index index` pair per line"),
+    ("--progress", "", "draw a progress bar on stderr"),
+    ("--progress-interval", "N", "faces removed between progress updates (default 1000)"),
+];
+
+const RATIO_OPTS: &[Opt] = &[
+    ("--ratio", "R", "keep R of the faces (default 0.5)"),
+    ("--target", "N", "keep N faces, overrides the ratio"),
+];
+
+const OUT_OPTS: &[Opt] = &[("--format", "F", "obj | ply, output format (default from extension)")];
+
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "simplify",
+        args: "<in> <out> [ratio]",
+        nargs: (2, 3),
+        about: "simplify a mesh by edge collapse or vertex clustering",
+        opts: &[
+            RATIO_OPTS,
+            &[
+                (
+                    "--method",
+                    "M",
+                    "qem | cluster (default qem); cluster keeps ratio x vertices, not faces",
+                ),
+                ("--out-of-core", "G", "stream the input through a G^3 clustering grid"),
+            ],
+            PRESET_OPTS,
+            QEM_OPTS,
+            VERTEX_OPTS,
+            OUT_OPTS,
+        ],
+    },
+    Command {
+        name: "batch",
+        args: "<in>... --out <dir>",
+        nargs: (1, usize::MAX),
+        about: "simplify files, directories or globs in parallel, mirroring them under <dir>",
+        opts: &[
+            &[
+                ("--out", "DIR", "output root, required"),
+                ("--report", "FILE", "write a .json or .csv report of every file"),
+                ("--jobs", "N", "files processed at the same time (default all cores)"),
+                ("--measure", "", "measure the surface distance of every result"),
+            ],
+            RATIO_OPTS,
+            PRESET_OPTS,
+            QEM_OPTS,
+            OUT_OPTS,
+        ],
+    },
+    Command {
+        name: "lod",
+        args: "<in> <out>",
+        nargs: (2, 2),
+        about: "write a chain of levels of detail to <out> with suffixes _lod1, _lod2, ...",
+        opts: &[
+            &[("--levels", "R,R,..", "face ratios of the levels (default 0.5,0.25,0.125)")],
+            PRESET_OPTS,
+            QEM_OPTS,
+            VERTEX_OPTS,
+            OUT_OPTS,
+        ],
+    },
+    Command {
+        name: "stats",
+        args: "<in>",
+        nargs: (1, 1),
+        about: "print counts, topology and geometry of a mesh",
+        opts: &[],
+    },
+    Command {
+        name: "convert",
+        args: "<in> <out>",
+        nargs: (2, 2),
+        about: "convert between OBJ and PLY",
+        opts: &[OUT_OPTS],
+    },
+    Command {
+        name: "compare",
+        args: "<a> <b>",
+        nargs: (2, 2),
+        about: "measure the surface distance between two meshes",
+        opts: &[&[("--threads", "N", "number of threads (default all cores)")]],
+    },
+    Command {
+        name: "repair",
+        args: "<in> <out>",
+        nargs: (2, 2),
+        about: "weld vertices, drop degenerate and duplicate faces and unused vertices",
+        opts: &[
+            &[("--weld", "EPS", "merge vertices closer than EPS (default 0, exact)")],
+            OUT_OPTS,
+        ],
+    },
+];
+
+struct Args {
+    pos: Vec<String>,
+    named: HashMap<&'static str, String>,
+    help: bool,
+}
+
+impl Args {
+    fn parse(cmd: &Command, raw: &[String]) -> Result<Self, Error> {
+        let mut ret = Self { pos: vec![], named: HashMap::new(), help: false };
+        let mut it = raw.iter();
+        while let Some(a) = it.next() {
+            if a == "-h" || a == "--help" {
+                ret.help = true;
+            } else if a.starts_with("--") {
+                // 同时支持 `--name value` 与 `--name=value`
+                let (name, inline) = match a.find('=') {
+                    Some(i) => (&a[..i], Some(a[i + 1..].to_string())),
+                    None => (a.as_str(), None),
+                };
+                let opt = cmd
+                    .opts
+                    .iter()
+                    .flat_map(|g| g.iter())
+                    .find(|o| o.0 == name)
+                    .ok_or_else(|| Error::Usage(format!("unknown option {}", name)))?;
+                let value = match (opt.1.is_empty(), inline) {
+                    (true, None) => String::new(),
+                    (true, Some(_)) => {
+                        return Err(Error::Usage(format!("{} does not take a value", name)))
+                    },
+                    (false, Some(v)) => v,
+                    (false, None) => it
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| Error::Usage(format!("missing value of {}", name)))?,
+                };
+                ret.named.insert(opt.0, value);
+            } else {
+                ret.pos.push(a.clone());
+            }
+        }
+        let (lo, hi) = cmd.nargs;
+        if !ret.help && (ret.pos.len() < lo || ret.pos.len() > hi) {
+            return Err(Error::Usage(format!("{} expects {}", cmd.name, cmd.args)));
+        }
+        Ok(ret)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.named.contains_key(name)
+    }
+
+    fn get<T: FromStr>(&self, name: &str) -> Result<Option<T>, Error>
+    where
+        T::Err: fmt::Display,
+    {
+        match self.named.get(name) {
+            Some(v) => v
+                .parse()
+                .map(Some)
+                .map_err(|e| Error::Usage(format!("invalid value of {}: {} ({})", name, v, e))),
+            None => Ok(None),
+        }
+    }
+
+    // 取值必须是 choices 之一，缺省时为第一个
+    fn choice(&self, name: &str, choices: &[&'static str]) -> Result<&'static str, Error> {
+        match self.named.get(name) {
+            Some(v) => choices.iter().find(|&&c| c == v).cloned().ok_or_else(|| {
+                Error::Usage(format!("{} must be one of {}", name, choices.join(", ")))
+            }),
+            None => Ok(choices[0]),
+        }
+    }
+
+    fn format(&self, path: &str) -> Result<Format, Error> {
+        if !self.flag("--format") {
+            return Ok(Format::from_ext(path).unwrap_or(Format::Obj));
+        }
+        Ok(match self.choice("--format", &["obj", "ply"])? {
+            "ply" => Format::Ply,
+            _ => Format::Obj,
+        })
+    }
+}
+
+fn help(cmd: Option<&Command>) -> String {
+    let cmd = match cmd {
+        Some(cmd) => cmd,
+        None => {
+            let mut s = format!("Usage: {} <command> [options]\n\nCommands:\n", BIN);
+            COMMANDS.iter().for_each(|c| s += &format!("  {:<9} {}\n", c.name, c.about));
+            s += &format!(
+                "\nRun `{} <command> --help` for the options of a command.\n\
+                 `{} <in> <out> [ratio] [options]` is the same as the simplify command.\n\
+                 Input formats are detected from the file header, output formats from the extension.\n\
+                 Exit status: 0 on success, 1 on failure, 2 on invalid arguments.",
+                BIN, BIN
+            );
+            return s;
+        },
+    };
+    let mut s = format!("Usage: {} {} {} [options]\n\n{}\n", BIN, cmd.name, cmd.args, cmd.about);
+    if !cmd.opts.is_empty() {
+        s += "\nOptions:\n";
+        cmd.opts.iter().flat_map(|g| g.iter()).for_each(|&(name, arg, about)| {
+            s += &format!("  {:<22} {}\n", format!("{} {}", name, arg), about);
+        });
+    }
+    s.trim_end().to_string()
+}
+
+// total 为 0 时取收到的最大面数
+fn progress_bar(total: usize) -> Arc<dyn Observer> {
+    let total = AtomicUsize::new(total);
+    Arc::new(move |p: &Progress| {
+        let total = total.fetch_max(p.faces, Ordering::Relaxed).max(p.faces);
+        let done =
+            total.saturating_sub(p.faces) as Flt / total.saturating_sub(p.target).max(1) as Flt;
+        let n = (done.min(1.0) * 40.0) as usize;
+        eprint!(
+            "\r[{}{}] {:3.0}% {} faces, error {:.3e}",
+            "#".repeat(n),
+            " ".repeat(40 - n),
+            done.min(1.0) * 100.0,
+            p.faces,
+            p.error
+        );
+        Control::Continue
+    })
+}
+
+fn check_ratio(r: Flt) -> Result<Flt, Error> {
+    match (0.0..=1.0).contains(&r) {
+        true => Ok(r),
+        false => Err(Error::Usage(format!("ratio must be in [0, 1], got {}", r))),
+    }
+}
+
+// 权重为负会颠倒代价的顺序，无穷大则使代价失去意义
+fn weight(a: &Args, name: &str) -> Result<Option<Flt>, Error> {
+    match a.get::<Flt>(name)? {
+        Some(w) if !w.is_finite() || w < 0.0 => {
+            Err(Error::Usage(format!("{} must be a finite non-negative number, got {}", name, w)))
+        },
+        w => Ok(w),
+    }
+}
+
+// 只允许 allowed 中的选项与 mode 一起使用，其余的选项在该模式下不起作用
+fn only(a: &Args, allowed: &[&str], mode: &str) -> Result<(), Error> {
+    let mut names: Vec<_> = a.named.keys().filter(|k| !allowed.contains(k)).collect();
+    names.sort();
+    match names.first() {
+        Some(name) => Err(Error::Usage(format!("{} cannot be used with {}", name, mode))),
+        None => Ok(()),
+    }
+}
+
+// 先取预设（没有时为默认值），再用命令行中给出的与网格无关的选项覆盖；
+// 简化比按 --ratio、位置参数 ratio 的顺序取第一个给出的，--target 在 mesh_options 中换算
+fn options(a: &Args, ratio: Option<&String>) -> Result<SimplifyOptions, Error> {
+    let mut opts = match a.named.get("--preset") {
+        Some(p) if p.to_lowercase().ends_with(".json") => SimplifyOptions::load_preset(p, None),
+        Some(p) => {
+            let file = a.named.get("--presets").map_or("presets.json", |f| f.as_str());
+            SimplifyOptions::load_preset(file, Some(p))
+        },
+        None => Ok(SimplifyOptions::default()),
+    }
+    .map_err(Error::Usage)?;
+    let ratio = ratio.map(|r| r.parse().map_err(|_| Error::Usage(format!("invalid ratio: {}", r))));
+    if let Some(r) = a.get("--ratio")?.or(ratio.transpose()?) {
+        opts.ratio = check_ratio(r)?;
+    }
+    a.get::<usize>("--target")?;
+    macro_rules! set {
+        ($name:expr, $field:ident) => {
+            if let Some(v) = a.get($name)? {
+                opts.$field = v;
+            }
+        };
+    }
+    if let Some(w) = weight(a, "--feature-weight")? {
+        opts.feature_weight = w;
+    }
+    if let Some(w) = weight(a, "--color-weight")? {
+        opts.color_weight = w;
+    }
+    if let Some(w) = weight(a, "--skin-penalty")? {
+        opts.skin_penalty = w;
+    }
+    set!("--placement", placement);
+    set!("--strategy", strategy);
+    set!("--quadric-weight", quadric_weight);
+    set!("--threads", threads);
+    set!("--progress-interval", progress_interval);
+    if a.flag("--normalize") {
+        opts.normalize = true;
+    }
+    if let Some(e) = a.get("--max-error")? {
+        opts.max_error = Some(e);
+    }
+    if let Some(d) = a.get("--feature-angle")? {
+        opts.feature_angle = Some(d);
+    }
+    match boundary(a)? {
+        Some("free") => opts.preserve_boundary = false,
+        Some("preserve") => opts.preserve_boundary = true,
+        _ => (),
+    }
+    Ok(opts)
+}
+
+fn boundary(a: &Args) -> Result<Option<&'static str>, Error> {
+    let b =
+        a.named.get("--boundary").map(|_| a.choice("--boundary", &["free", "preserve", "lock"]));
+    b.transpose()
+}
+
+// 在 options 的结果上加上依赖具体网格的部分：--target、顶点标记、折痕边、锁定的边界与进度条
+fn mesh_options(
+    a: &Args,
+    opts: &SimplifyOptions,
+    mesh: &Mesh,
+    source: &VertexSource,
+) -> Result<SimplifyOptions, Error> {
+    let mut opts = opts.clone();
+    if let Some(n) = a.get::<usize>("--target")? {
+        opts.ratio = (n as Flt / mesh.tri.len().max(1) as Flt).min(1.0);
+    }
+    if let Some(path) = a.named.get("--weights") {
+        opts.load_vertex_marks(path, source).map_err(Error::Usage)?;
+    }
+    if let Some(path) = a.named.get("--creases") {
+        opts.load_crease_edges(path, source).map_err(Error::Usage)?;
+    }
+    if boundary(a)? == Some("lock") {
+        opts.locked.resize(mesh.pos.len(), false);
+        mesh.edge_faces().iter().filter(|(_, f)| f.len() == 1).for_each(|(&(u, v), _)| {
+            opts.locked[u] = true;
+            opts.locked[v] = true;
+        });
+    }
+    if a.flag("--progress") {
+        opts.observer = Some(progress_bar(mesh.tri.len()));
+    }
+    opts.validate(mesh.pos.len()).map_err(Error::Usage)?;
+    Ok(opts)
+}
+
+fn load(path: &str) -> Result<Mesh, Error> {
+    Mesh::try_load_indexed(path).map(|(mesh, _)| mesh).map_err(Error::Run)
+}
+
+fn save(mesh: &Mesh, path: &str, format: Format) -> Result<(), Error> {
+    mesh.try_save_as(path, format).map_err(Error::Run)
+}
+
+fn save_preset(a: &Args, opts: &SimplifyOptions) -> Result<(), Error> {
+    match a.named.get("--save-preset") {
+        Some(path) => fs::write(path, opts.to_json().pretty() + "\n")
+            .map_err(|e| Error::Run(format!("cannot write {}: {}", path, e))),
+        None => Ok(()),
+    }
+}
+
+fn simplify_mesh(a: &Args, mesh: &Mesh, opts: &SimplifyOptions) -> Mesh {
+    let out = match a.named.get("--method").map(|m| m.as_str()) {
+        Some("cluster") => {
+            let n = (mesh.pos.len() as Flt * opts.ratio).round() as usize;
+            mesh.cluster(ClusterSize::Vertices(n), opts.quadric_weight)
+        },
+        _ => mesh.simplify_with(opts),
+    };
+    if a.flag("--progress") {
+        eprintln!();
+    }
+    out
+}
+
+fn simplify(a: &Args) -> Result<(), Error> {
+    let (input, output) = (&a.pos[0], &a.pos[1]);
+    let format = a.format(output)?;
+    let method = a.choice("--method", &["qem", "cluster"])?;
+    if let Some(grid) = a.get::<usize>("--out-of-core")? {
+        // 外存聚类只用到格子数与误差矩阵的加权方式，不读预设，也没有简化比
+        only(a, &["--out-of-core", "--quadric-weight", "--progress", "--format"], "--out-of-core")?;
+        if a.pos.len() > 2 {
+            return Err(Error::Usage("a ratio cannot be used with --out-of-core".to_string()));
+        }
+        if grid == 0 {
+            return Err(Error::Usage("--out-of-core needs at least 1 cell".to_string()));
+        }
+        let bar = a.flag("--progress").then(|| progress_bar(0));
+        let weight = a.get("--quadric-weight")?.unwrap_or(NormalWeight::Uniform);
+        let out = simplify_out_of_core(input, grid, weight, bar.as_deref());
+        if bar.is_some() {
+            eprintln!();
+        }
+        return save(&out, output, format);
+    }
+    if method == "cluster" {
+        let allowed = [
+            "--method",
+            "--ratio",
+            "--target",
+            "--quadric-weight",
+            "--preset",
+            "--presets",
+            "--save-preset",
+            "--format",
+        ];
+        only(a, &allowed, "--method cluster")?;
+    }
+    let opts = options(a, a.pos.get(2))?;
+    save_preset(a, &opts)?;
+    let (mesh, source) = Mesh::try_load_indexed(input).map_err(Error::Run)?;
+    let opts = mesh_options(a, &opts, &mesh, &source)?;
+    save(&simplify_mesh(a, &mesh, &opts), output, format)
+}
+
+fn lod(a: &Args) -> Result<(), Error> {
+    let (input, output) = (&a.pos[0], &a.pos[1]);
+    let format = a.format(output)?;
+    let levels = a.named.get("--levels").map_or("0.5,0.25,0.125", |l| l.as_str());
+    let levels = levels
+        .split(',')
+        .map(|r| r.trim().parse().map_err(|_| Error::Usage(format!("invalid level: {}", r))))
+        .map(|r| r.and_then(check_ratio))
+        .collect::<Result<Vec<Flt>, _>>()?;
+    let opts = options(a, None)?;
+    save_preset(a, &opts)?;
+    let (mesh, source) = Mesh::try_load_indexed(input).map_err(Error::Run)?;
+    let mut opts = mesh_options(a, &opts, &mesh, &source)?;
+    let path = Path::new(output);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let ext =
+        path.extension().and_then(|s| s.to_str()).map_or(String::new(), |e| format!(".{}", e));
+    // 每一级都从原网格简化，保证顶点权重与锁定对每一级都有效
+    for (k, &r) in levels.iter().enumerate() {
+        opts.ratio = r;
+        let name = path.with_file_name(format!("{}_lod{}{}", stem, k + 1, ext));
+        let out = simplify_mesh(a, &mesh, &opts);
+        println!("lod{}: {} faces", k + 1, out.tri.len());
+        save(&out, name.to_str().unwrap(), format)?;
+    }
+    Ok(())
+}
+
+fn batch(a: &Args) -> Result<(), Error> {
+    let out = a.named.get("--out").ok_or_else(|| Error::Usage("batch needs --out".to_string()))?;
+    let format = match a.flag("--format") {
+        true => Some(a.format("")?),
+        false => None,
+    };
+    // 参数只解析一遍，各个文件只加上依赖网格的部分
+    let opts = options(a, None)?;
+    save_preset(a, &opts)?;
+    let jobs =
+        a.get("--jobs")?.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    let files: Vec<_> = collect_inputs(&a.pos)
+        .map_err(Error::Run)?
+        .into_iter()
+        .map(|(input, rel)| {
+            let mut output = Path::new(out).join(rel);
+            match format {
+                Some(Format::Ply) => output.set_extension("ply"),
+                Some(Format::Obj) => output.set_extension("obj"),
+                None => true,
+            };
+            (input, output)
+        })
+        .collect();
+    let count = AtomicUsize::new(0);
+    let reports = simplify_batch(
+        &files,
+        jobs,
+        format,
+        a.flag("--measure"),
+        |mesh, source| match mesh_options(a, &opts, mesh, source) {
+            Ok(opts) => Ok(opts),
+            Err(Error::Usage(e)) | Err(Error::Run(e)) => Err(e),
+        },
+        |r| {
+            let k = count.fetch_add(1, Ordering::SeqCst) + 1;
+            match &r.error {
+                Some(e) => eprintln!("[{}/{}] {} failed: {}", k, files.len(), r.input.display(), e),
+                None => println!(
+                    "[{}/{}] {} -> {}: {} -> {} faces, {:.2}s",
+                    k,
+                    files.len(),
+                    r.input.display(),
+                    r.output.display(),
+                    r.in_faces,
+                    r.out_faces,
+                    r.seconds
+                ),
+            }
+        },
+    );
+    let failed = reports.iter().filter(|r| r.error.is_some()).count();
+    println!("{} files, {} failed", reports.len(), failed);
+    if let Some(path) = a.named.get("--report") {
+        let s = match path.to_lowercase().ends_with(".csv") {
+            true => report_csv(&reports),
+            false => report_json(&reports).pretty() + "\n",
+        };
+        fs::write(path, s).map_err(|e| Error::Run(format!("cannot write {}: {}", path, e)))?;
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(Error::Run(format!("{} of {} files failed", failed, reports.len()))),
+    }
+}
+
+fn run(raw: &[String]) -> Result<(), Error> {
+    let first = match raw.first() {
+        Some(f) if f != "-h" && f != "--help" && f != "help" => f,
+        _ => {
+            println!("{}", help(None));
+            return Ok(());
+        },
+    };
+    let (cmd, rest) = match COMMANDS.iter().find(|c| c.name == first) {
+        Some(cmd) => (cmd, &raw[1..]),
+        // 兼容旧的用法：mesh_simplification in.obj out.obj 0.3
+        None if Path::new(first).is_file() => (&COMMANDS[0], raw),
+        None => return Err(Error::Usage(format!("unknown command {}", first))),
+    };
+    let a = Args::parse(cmd, rest)?;
+    if a.help {
+        println!("{}", help(Some(cmd)));
+        return Ok(());
+    }
+    match cmd.name {
+        "simplify" => simplify(&a),
+        "lod" => lod(&a),
+        "batch" => batch(&a),
+        "stats" => {
+            let mesh = load(&a.pos[0])?;
+            println!("{}", mesh.stats());
+            println!("normals:               {}", mesh.has_normals());
+            println!("colors:                {}", mesh.has_colors());
+            println!("{}", mesh.triangle_quality());
+            Ok(())
+        },
+        "convert" => {
+            let format = a.format(&a.pos[1])?;
+            save(&load(&a.pos[0])?, &a.pos[1], format)
+        },
+        "compare" => {
+            let cores = thread::available_parallelism().map_or(1, |n| n.get());
+            let threads = a.get("--threads")?.unwrap_or(cores);
+            let (m1, m2) = (load(&a.pos[0])?, load(&a.pos[1])?);
+            let diag = m1.bbox().diagonal();
+            let (d1, d2) = (m1.distance_to(&m2, threads), m2.distance_to(&m1, threads));
+            println!("a -> b:    {}", d1);
+            println!("b -> a:    {}", d2);
+            println!(
+                "hausdorff: {:.6e} ({:.4}% of the diagonal of a)",
+                d1.max.max(d2.max),
+                d1.max.max(d2.max) / diag * 100.0
+            );
+            Ok(())
+        },
+        "repair" => {
+            let format = a.format(&a.pos[1])?;
+            let weld = a.get("--weld")?.unwrap_or(0.0);
+            let (out, r) = load(&a.pos[0])?.repair(weld);
+            println!(
+                "merged {} vertices, removed {} degenerate faces, {} duplicate faces, {} unreferenced vertices",
+                r.merged_vertices, r.degenerate_faces, r.duplicate_faces, r.unreferenced_vertices
+            );
+            save(&out, &a.pos[1], format)
+        },
+        _ => Err(Error::Run(format!("command {} is not implemented", cmd.name))),
+    }
+}
+
+// 最先发生的 panic 的信息，由 main 输出一次；batch 中各文件的 panic 已记入报告，不再另外输出
+static PANIC: Mutex<Option<String>> = Mutex::new(None);
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 4 {
-        println!("Invalid arguments.\n./mesh_simplication [in.obj] [out.obj] 0.3");
-    } else {
-        Mesh::new(&args[1])
-            .simplify(args[3].parse::<Flt>().unwrap())
-            .save(&args[2]);
+    let raw: Vec<String> = env::args().skip(1).collect();
+    panic::set_hook(Box::new(|info| {
+        let msg = match info.payload().downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => info.payload().downcast_ref::<String>().cloned().unwrap_or_default(),
+        };
+        if let Ok(mut p) = PANIC.lock() {
+            p.get_or_insert(msg);
+        }
+    }));
+    let code = match panic::catch_unwind(|| run(&raw)) {
+        Ok(Ok(())) => 0,
+        Ok(Err(Error::Usage(msg))) => {
+            eprintln!("error: {}\nRun `{} --help` for usage.", msg, BIN);
+            2
+        },
+        Ok(Err(Error::Run(msg))) => {
+            eprintln!("error: {}", msg);
+            1
+        },
+        Err(_) => {
+            eprintln!(
+                "error: {}",
+                PANIC.lock().map(|p| p.clone()).unwrap_or_default().unwrap_or_default()
+            );
+            1
+        },
+    };
+    process::exit(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn parse() {
+        let a =
+            Args::parse(&COMMANDS[0], &args("in.obj out.ply --ratio=0.3 --progress --threads 4"))
+                .unwrap();
+        assert_eq!(a.pos, args("in.obj out.ply"));
+        assert_eq!(a.get::<Flt>("--ratio"), Ok(Some(0.3)));
+        assert_eq!(a.get::<usize>("--threads"), Ok(Some(4)));
+        assert!(a.flag("--progress") && !a.flag("--weights"));
+        assert_eq!(a.format("out.ply"), Ok(Format::Ply));
+        assert_eq!(a.choice("--placement", &["optimal", "best"]), Ok("optimal"));
+        let usage = |s: &str| matches!(Args::parse(&COMMANDS[0], &args(s)), Err(Error::Usage(_)));
+        assert!(usage("in.obj"));
+        assert!(usage("in.obj out.obj --bogus 1"));
+        assert!(usage("in.obj out.obj --threads"));
+        assert!(usage("in.obj out.obj --progress=1"));
+        let a = Args::parse(&COMMANDS[0], &args("a b --ratio x --placement worst")).unwrap();
+        assert!(a.get::<Flt>("--ratio").is_err());
+        assert!(a.choice("--placement", &["optimal", "best"]).is_err());
+        assert_eq!(run(&args("nope")), Err(Error::Usage("unknown command nope".to_string())));
+        // 在所选的方法下不起作用的选项与不合法的权重都在读入网格之前报错
+        let usage = |s: &str| matches!(run(&args(s)), Err(Error::Usage(_)));
+        assert!(usage("simplify a.obj b.obj --out-of-core 64 --preset props"));
+        assert!(usage("simplify a.obj b.obj 0.3 --out-of-core 64"));
+        assert!(usage("simplify a.obj b.obj --out-of-core 0"));
+        assert!(usage("simplify a.obj b.obj --method cluster --feature-angle 30"));
+        assert!(usage("simplify a.obj b.obj --color-weight -1"));
+        assert!(usage("simplify a.obj b.obj --skin-penalty inf"));
+        let run_error = |s: &str| matches!(run(&args(s)), Err(Error::Run(_)));
+        assert!(run_error("simplify a.obj b.obj --progress-interval 10 --color-weight 0.1"));
     }
 }
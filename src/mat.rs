@@ -1,13 +1,14 @@
-use crate::{Flt, Vct, EPS};
+use crate::float::Float;
+use crate::{Vct, EPS};
 use std::fmt;
 use std::ops::{Add, AddAssign, Index, IndexMut, Mul, Sub, SubAssign};
 
 #[derive(Copy, Clone, PartialEq, Debug, Default)]
-pub struct Mat {
-    pub data: [[Flt; 4]; 4],
+pub struct Mat<T = f64> {
+    pub data: [[T; 4]; 4],
 }
 
-impl fmt::Display for Mat {
+impl<T: Float> fmt::Display for Mat<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut res = String::new();
         for i in 0..4 {
@@ -20,30 +21,30 @@ impl fmt::Display for Mat {
     }
 }
 
-impl Index<usize> for Mat {
-    type Output = [Flt; 4];
+impl<T> Index<usize> for Mat<T> {
+    type Output = [T; 4];
 
-    fn index(&self, idx: usize) -> &[Flt; 4] {
+    fn index(&self, idx: usize) -> &[T; 4] {
         &self.data[idx]
     }
 }
 
-impl IndexMut<usize> for Mat {
+impl<T> IndexMut<usize> for Mat<T> {
     fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
         &mut self.data[idx]
     }
 }
 
-impl Add<Mat> for Mat {
+impl<T: Float> Add<Mat<T>> for Mat<T> {
     type Output = Self;
     fn add(self, rhs: Self) -> Self {
-        let mut ret = self.clone();
+        let mut ret = self;
         ret += rhs;
         ret
     }
 }
 
-impl AddAssign<Mat> for Mat {
+impl<T: Float> AddAssign<Mat<T>> for Mat<T> {
     fn add_assign(&mut self, rhs: Self) {
         for i in 0..4 {
             for j in 0..4 {
@@ -53,16 +54,16 @@ impl AddAssign<Mat> for Mat {
     }
 }
 
-impl Sub<Mat> for Mat {
+impl<T: Float> Sub<Mat<T>> for Mat<T> {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self {
-        let mut ret = self.clone();
+        let mut ret = self;
         ret -= rhs;
         ret
     }
 }
 
-impl SubAssign<Mat> for Mat {
+impl<T: Float> SubAssign<Mat<T>> for Mat<T> {
     fn sub_assign(&mut self, rhs: Self) {
         for i in 0..4 {
             for j in 0..4 {
@@ -72,13 +73,26 @@ impl SubAssign<Mat> for Mat {
     }
 }
 
-impl Mul<Mat> for Mat {
+impl<T: Float> Mul<T> for Mat<T> {
+    type Output = Self;
+    fn mul(self, rhs: T) -> Self {
+        let mut ret = self;
+        for i in 0..4 {
+            for j in 0..4 {
+                ret[i][j] *= rhs;
+            }
+        }
+        ret
+    }
+}
+
+impl<T: Float> Mul<Mat<T>> for Mat<T> {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self {
         let mut ret = Self::default();
         for i in 0..4 {
             for j in 0..4 {
-                let mut tmp = 0.0;
+                let mut tmp = T::ZERO;
                 for k in 0..4 {
                     tmp += self[i][k] * rhs[k][j];
                 }
@@ -89,27 +103,132 @@ impl Mul<Mat> for Mat {
     }
 }
 
-impl Mat {
+impl<T: Float> Mat<T> {
     pub fn identity() -> Self {
-        return Self {
-            data: [
-                [1.0, 0.0, 0.0, 0.0],
-                [0.0, 1.0, 0.0, 0.0],
-                [0.0, 0.0, 1.0, 0.0],
-                [0.0, 0.0, 0.0, 1.0],
-            ],
-        };
+        let mut ret = Self::default();
+        for i in 0..4 {
+            ret[i][i] = T::ONE;
+        }
+        ret
+    }
+
+    pub fn cast<U: Float>(&self) -> Mat<U> {
+        let mut ret = Mat::default();
+        for i in 0..4 {
+            for j in 0..4 {
+                ret[i][j] = U::from_f64(self[i][j].to_f64());
+            }
+        }
+        ret
+    }
+
+    pub fn translate(v: Vct<T>) -> Self {
+        let mut ret = Self::identity();
+        ret[0][3] = v.x;
+        ret[1][3] = v.y;
+        ret[2][3] = v.z;
+        ret
     }
 
-    pub fn multiply_by_vct(&self, v: Vct) -> Flt {
+    pub fn scale(v: Vct<T>) -> Self {
+        let mut ret = Self::identity();
+        ret[0][0] = v.x;
+        ret[1][1] = v.y;
+        ret[2][2] = v.z;
+        ret
+    }
+
+    // 绕过原点的 axis 旋转 angle（弧度，右手定则），Rodrigues 公式
+    pub fn rotate(axis: Vct<T>, angle: T) -> Self {
+        let a = axis.norm();
+        let (s, c) = (angle.sin(), angle.cos());
+        let k = [[T::ZERO, -a.z, a.y], [a.z, T::ZERO, -a.x], [-a.y, a.x, T::ZERO]];
+        let (a, mut ret) = ([a.x, a.y, a.z], Self::identity());
+        for i in 0..3 {
+            for j in 0..3 {
+                let id = if i == j { T::ONE } else { T::ZERO };
+                ret[i][j] = c * id + (T::ONE - c) * a[i] * a[j] + s * k[i][j];
+            }
+        }
+        ret
+    }
+
+    // 观察矩阵：eye 变到原点，视线方向变到 -z，up 变到 yz 平面内
+    pub fn look_at(eye: Vct<T>, target: Vct<T>, up: Vct<T>) -> Self {
+        let f = (target - eye).norm();
+        let s = (f % up).norm();
+        let u = s % f;
+        let mut ret = Self::identity();
+        for (i, r) in [s, u, -f].iter().enumerate() {
+            ret[i] = [r.x, r.y, r.z, -r.dot(eye)];
+        }
+        ret
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut ret = *self;
+        for i in 0..4 {
+            for j in 0..4 {
+                ret[i][j] = self[j][i];
+            }
+        }
+        ret
+    }
+
+    pub fn det(&self) -> T {
+        let a = &self.data;
+        let s0 = a[0][0] * a[1][1] - a[1][0] * a[0][1];
+        let s1 = a[0][0] * a[1][2] - a[1][0] * a[0][2];
+        let s2 = a[0][0] * a[1][3] - a[1][0] * a[0][3];
+        let s3 = a[0][1] * a[1][2] - a[1][1] * a[0][2];
+        let s4 = a[0][1] * a[1][3] - a[1][1] * a[0][3];
+        let s5 = a[0][2] * a[1][3] - a[1][2] * a[0][3];
+        let c5 = a[2][2] * a[3][3] - a[3][2] * a[2][3];
+        let c4 = a[2][1] * a[3][3] - a[3][1] * a[2][3];
+        let c3 = a[2][1] * a[3][2] - a[3][1] * a[2][2];
+        let c2 = a[2][0] * a[3][3] - a[3][0] * a[2][3];
+        let c1 = a[2][0] * a[3][2] - a[3][0] * a[2][2];
+        let c0 = a[2][0] * a[3][1] - a[3][0] * a[2][1];
+        s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0
+    }
+
+    // 点的第四维为 1，投影变换时除以变换后的第四维
+    pub fn transform_point(&self, v: Vct<T>) -> Vct<T> {
+        let w = self[3][0] * v.x + self[3][1] * v.y + self[3][2] * v.z + self[3][3];
+        let p = self.mul_vct3(v) + Vct::new(self[0][3], self[1][3], self[2][3]);
+        if w == T::ONE || w == T::ZERO {
+            p
+        } else {
+            p / w
+        }
+    }
+
+    // 方向向量不受平移影响
+    pub fn transform_vector(&self, v: Vct<T>) -> Vct<T> {
+        self.mul_vct3(v)
+    }
+
+    // 变换法向的矩阵：左上角 3x3 部分的余子式矩阵，等于逆矩阵的转置乘以行列式，
+    // 所以矩阵奇异时也有定义，变换后的法向需要再单位化
+    pub fn normal_matrix(&self) -> Self {
+        let r = |i: usize| Vct::new(self[i][0], self[i][1], self[i][2]);
+        let mut ret = Self::identity();
+        for (i, c) in [r(1) % r(2), r(2) % r(0), r(0) % r(1)].iter().enumerate() {
+            ret[i] = [c.x, c.y, c.z, T::ZERO];
+        }
+        ret
+    }
+
+    pub fn multiply_by_vct(&self, v: Vct<T>) -> T {
         // vQv^T
-        let mut tmp = [0.0, 0.0, 0.0, 0.0];
+        let v = [v.x, v.y, v.z, T::ONE];
+        let mut tmp = [T::ZERO; 4];
         for i in 0..4 {
             for j in 0..4 {
                 tmp[i] += v[j] * self[j][i];
             }
         }
-        let mut ret = 0.0;
+        let mut ret = T::ZERO;
         for i in 0..4 {
             ret += tmp[i] * v[i];
         }
@@ -117,17 +236,16 @@ impl Mat {
     }
 
     pub fn split(&self) -> Self {
-        let mut ret = self.clone();
-        ret[3][0] = 0.0;
-        ret[3][1] = 0.0;
-        ret[3][2] = 0.0;
-        ret[3][3] = 1.0;
+        let mut ret = *self;
+        ret[3] = [T::ZERO, T::ZERO, T::ZERO, T::ONE];
         ret
     }
 
     pub fn inverse(&self) -> Option<Self> {
-        let mut a = self.clone();
+        let mut a = *self;
         let mut b = Self::identity();
+        // 主元相对于最大元素过小时视为奇异，与矩阵的尺度无关
+        let scale = self.data.iter().flatten().fold(T::ZERO, |m, x| m.max(x.abs()));
         for x in 0..4 {
             let mut z = x;
             for i in x + 1..4 {
@@ -135,7 +253,7 @@ impl Mat {
                     z = i;
                 }
             }
-            if a[z][x].abs() <= EPS {
+            if a[z][x].abs() <= T::from_f64(EPS) * scale {
                 return None;
             }
             if z != x {
@@ -150,33 +268,100 @@ impl Mat {
                     b[x][y] = t;
                 }
             }
-            let inv = -1.0 / a[x][x];
+            let inv = -T::ONE / a[x][x];
             for i in 0..4 {
                 if i == x {
                     continue;
                 }
                 let d = inv * a[i][x];
+                let (ax, bx) = (a[x], b[x]);
                 for y in x..4 {
-                    a[i][y] += d * a[x][y];
+                    a[i][y] += d * ax[y];
                 }
                 for y in 0..4 {
-                    b[i][y] += d * b[x][y];
+                    b[i][y] += d * bx[y];
                 }
             }
         }
         for x in 0..4 {
-            let inv = 1.0 / a[x][x];
+            let inv = T::ONE / a[x][x];
             for y in 0..4 {
                 b[x][y] *= inv;
             }
         }
         Some(b)
     }
+
+    // 左上角 3x3 对称部分的特征分解（Jacobi 旋转），返回特征值与对应的单位特征向量
+    pub fn eigen3(&self) -> ([T; 3], [Vct<T>; 3]) {
+        let (zero, one, half) = (T::ZERO, T::ONE, T::from_f64(0.5));
+        let mut a = [[zero; 3]; 3];
+        let mut v = [[one, zero, zero], [zero, one, zero], [zero, zero, one]];
+        for i in 0..3 {
+            for j in 0..3 {
+                a[i][j] = (self[i][j] + self[j][i]) * half;
+            }
+        }
+        for _ in 0..50 {
+            let off = a[0][1].abs() + a[0][2].abs() + a[1][2].abs();
+            if off <= T::from_f64(1e-30) {
+                break;
+            }
+            for &(p, q) in &[(0, 1), (0, 2), (1, 2)] {
+                if a[p][q].abs() <= T::from_f64(1e-300) {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (a[p][q] + a[p][q]);
+                let sign = if theta < zero { -one } else { one };
+                let t = sign / (theta.abs() + (theta * theta + one).sqrt());
+                let c = one / (t * t + one).sqrt();
+                let s = t * c;
+                for row in a.iter_mut().chain(v.iter_mut()) {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+                let (rp, rq) = (a[p], a[q]);
+                for k in 0..3 {
+                    a[p][k] = c * rp[k] - s * rq[k];
+                    a[q][k] = s * rp[k] + c * rq[k];
+                }
+            }
+        }
+        let vec = |i: usize| Vct::new(v[0][i], v[1][i], v[2][i]);
+        ([a[0][0], a[1][1], a[2][2]], [vec(0), vec(1), vec(2)])
+    }
+
+    // 左上角 3x3 部分乘以 v
+    pub fn mul_vct3(&self, v: Vct<T>) -> Vct<T> {
+        Vct::new(
+            self[0][0] * v.x + self[0][1] * v.y + self[0][2] * v.z,
+            self[1][0] * v.x + self[1][1] * v.y + self[1][2] * v.z,
+            self[2][0] * v.x + self[2][1] * v.y + self[2][2] * v.z,
+        )
+    }
+
+    // 用特征分解解左上角 3x3 对称部分的方程 A x = b：特征值小于 rcond 倍最大特征值的方向
+    // 视为奇异（截断的 SVD），在这些方向上取离 x0 最近的点；同时返回条件数 |λ|max / |λ|min，奇异时为无穷大
+    pub fn solve3(&self, b: Vct<T>, x0: Vct<T>, rcond: T) -> (Vct<T>, T) {
+        let (val, vec) = self.eigen3();
+        let max = val.iter().fold(T::ZERO, |m, x| m.max(x.abs()));
+        let min = val.iter().fold(T::INFINITY, |m, x| m.min(x.abs()));
+        let r = b - self.mul_vct3(x0);
+        let mut x = x0;
+        for i in 0..3 {
+            if max > T::ZERO && val[i].abs() > max * rcond {
+                x += vec[i] * (vec[i].dot(r) / val[i]);
+            }
+        }
+        (x, if min > T::ZERO { max / min } else { T::INFINITY })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{Flt, PI};
 
     #[test]
     fn inverse() {
@@ -194,17 +379,90 @@ mod tests {
                 [0.000, 0.000, 0.000, 1.000],
             ],
         };
-        let b = a.inverse().unwrap();
-        let c = a * b;
-        for i in 0..4 {
-            for j in 0..4 {
-                if i == j {
-                    assert!((c[i][j] - 1.0).abs() < EPS);
-                } else {
-                    assert!(c[i][j].abs() < EPS);
-                }
-            }
+        let identity = |c: Mat| {
+            (0..4).all(|i| (0..4).all(|j| (c[i][j] - if i == j { 1.0 } else { 0.0 }).abs() < EPS))
+        };
+        assert!(identity(a * a.inverse().unwrap()));
+        // 奇异的判断是相对于最大元素的，与矩阵的尺度无关
+        let small = a * 1e-9;
+        assert!(identity(small * small.inverse().unwrap()));
+        assert!((Mat::identity() * 1e-6).inverse().is_some());
+        let mut big = Mat::identity() * 1e6;
+        big[2][2] = 1.0;
+        assert!(big.inverse().is_none());
+        let mut flat = Mat::identity();
+        flat[2][2] = 1e-9;
+        assert!(flat.inverse().is_none());
+    }
+
+    #[test]
+    fn eigen3() {
+        let a = Mat {
+            data: [
+                [4.0, 1.0, -2.0, 0.0],
+                [1.0, 2.0, 0.5, 0.0],
+                [-2.0, 0.5, 3.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+        let (val, vec) = a.eigen3();
+        for i in 0..3 {
+            assert!((a.mul_vct3(vec[i]) - vec[i] * val[i]).len() < EPS);
+            assert!((vec[i].len() - 1.0).abs() < EPS);
         }
     }
 
+    #[test]
+    fn solve3() {
+        let a = Mat {
+            data: [
+                [4.0, 1.0, -2.0, 0.0],
+                [1.0, 2.0, 0.5, 0.0],
+                [-2.0, 0.5, 3.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+        let x = Vct::new(0.5, -1.0, 2.0);
+        let (y, cond) = a.solve3(a.mul_vct3(x), Vct::zero(), 1e-6);
+        assert!((y - x).len() < EPS);
+        let (val, _) = a.eigen3();
+        let abs = val.map(Flt::abs);
+        let (lo, hi) = (
+            abs.iter().cloned().fold(Flt::INFINITY, Flt::min),
+            abs.iter().cloned().fold(0.0, Flt::max),
+        );
+        assert!((cond - hi / lo).abs() < EPS && cond > 1.0);
+        // 近乎奇异：第三个方向的特征值只有 1e-9，被截断后取离 x0 最近的点
+        let mut b = Mat::identity();
+        b[2][2] = 1e-9;
+        let (y, cond) = b.solve3(Vct::new(1.0, 2.0, 1.0), Vct::new(0.0, 0.0, 5.0), 1e-3);
+        assert!((y - Vct::new(1.0, 2.0, 5.0)).len() < EPS);
+        assert!(cond > 1e8);
+    }
+
+    #[test]
+    fn transform() {
+        let m = Mat::translate(Vct::new(1.0, 2.0, 3.0))
+            * Mat::rotate(Vct::new(0.0, 0.0, 2.0), PI / 2.0)
+            * Mat::scale(Vct::new(2.0, 2.0, 2.0));
+        let p = m.transform_point(Vct::new(1.0, 0.0, 0.0));
+        assert!((p - Vct::new(1.0, 4.0, 3.0)).len() < EPS);
+        let v = m.transform_vector(Vct::new(1.0, 0.0, 0.0));
+        assert!((v - Vct::new(0.0, 2.0, 0.0)).len() < EPS);
+        assert!((m.det() - 8.0).abs() < EPS);
+        assert!((Mat::scale(Vct::new(1.0, -1.0, 1.0)).det() + 1.0).abs() < EPS);
+        let inv = m.inverse().unwrap();
+        let t = m.normal_matrix() * (1.0 / m.det());
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((m.transpose()[j][i] - m[i][j]).abs() < EPS);
+                assert!((t[i][j] - inv[j][i]).abs() < EPS);
+            }
+        }
+        let eye = Vct::new(3.0, 1.0, 2.0);
+        let view = Mat::look_at(eye, Vct::new(3.0, 1.0, -5.0), Vct::new(0.0, 1.0, 0.0));
+        assert!(view.transform_point(eye).len() < EPS);
+        let p = view.transform_point(Vct::new(4.0, 2.0, 0.0));
+        assert!((p - Vct::new(1.0, 1.0, -2.0)).len() < EPS);
+    }
 }
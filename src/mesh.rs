@@ -1,307 +1,584 @@
-use crate::{Flt, Mat, Vct};
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use crate::float::Float;
+use crate::{Flt, Mat, Skin, Vct};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Write};
 
-const COST_EPS: Flt = 1e50;
-const DIST_EPS: Flt = 1e50;
+pub type Tri = (usize, usize, usize);
 
 #[derive(Clone, Copy, Debug, PartialEq)]
-struct Edge {
-    pub v1: usize,
-    pub v2: usize,
-    pub qv: Mat,
-    pub v: Vct,
-    pub cost: Flt,
+pub enum Format {
+    Obj,
+    Ply,
 }
 
-impl Edge {
-    pub fn new(v1: usize, v2: usize, qv: Mat, v: Vct, cost: Flt) -> Self {
-        Self { v1, v2, qv, v, cost }
-    }
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct State {
-    pub cost: Flt,
-    pub index: usize,
-}
-
-impl State {
-    pub fn new(cost: Flt, index: usize) -> Self {
-        Self { cost, index }
+impl Format {
+    pub fn from_ext(path: &str) -> Option<Self> {
+        match path.rsplit('.').next()?.to_lowercase().as_str() {
+            "obj" => Some(Format::Obj),
+            "ply" => Some(Format::Ply),
+            _ => None,
+        }
     }
-}
 
-impl Eq for State {}
-
-impl Ord for State {
-    fn cmp(&self, other: &State) -> Ordering {
-        other.partial_cmp(self).unwrap()
+    // 读入时按文件头判断格式，与扩展名无关
+    pub fn detect(path: &str) -> Self {
+        let mut head = [0; 4];
+        match File::open(path).and_then(|mut f| f.read_exact(&mut head)) {
+            Ok(_) if head[..3] == *b"ply" && head[3].is_ascii_whitespace() => Format::Ply,
+            Ok(_) => Format::Obj,
+            Err(_) => Self::from_ext(path).unwrap_or(Format::Obj),
+        }
     }
 }
 
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &State) -> Option<Ordering> {
-        other.cost.partial_cmp(&self.cost)
-    }
+// 读入的顶点在文件中的来源：src[i] 为第 i 个顶点在文件中的下标（从 0 开始），count 为文件中的顶点数
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VertexSource {
+    pub src: Vec<usize>,
+    pub count: usize,
 }
 
-type Tri = (usize, usize, usize);
-
 #[derive(Clone, Debug)]
-pub struct Mesh {
-    pub pos: Vec<Vct>,
+pub struct Mesh<T = Flt> {
+    pub pos: Vec<Vct<T>>,
     pub tri: Vec<Tri>,
+    pub nrm: Vec<Vct<T>>,
+    pub nrm_tri: Vec<Tri>,
+    // 逐顶点的骨骼权重，为空表示没有蒙皮
+    pub skin: Vec<Skin>,
+    // 逐顶点的 RGB 颜色，分量在 [0, 1] 内，为空表示没有颜色
+    pub color: Vec<Vct<T>>,
 }
 
 impl Mesh {
     pub fn new(path: &str) -> Self {
-        let (pos, tri) = Self::load(path);
-        Self { pos, tri }
+        Self::load(path)
     }
+}
 
-    fn cal_q(v1: &Vct, v2: &Vct, v3: &Vct) -> Mat {
-        let mut q = Mat::default();
-        let norm = ((*v1 - *v3) % (*v2 - *v3)).norm();
-        let v = [norm.x, norm.y, norm.z, -norm.dot(*v3)];
-        for i in 0..4 {
-            for j in 0..4 {
-                q[i][j] = v[i] * v[j];
-            }
-        }
-        q
+impl<T: Float> Mesh<T> {
+    pub fn from_raw(pos: Vec<Vct<T>>, tri: Vec<Tri>) -> Self {
+        Self { pos, tri, nrm: vec![], nrm_tri: vec![], skin: vec![], color: vec![] }
     }
 
-    pub fn simplify(&self, ratio: Flt) -> Self {
-        let (mut pos, mut tri) = (self.pos.clone(), self.tri.clone());
-        let mut q = vec![Mat::default(); pos.len()];
-        let mut qp = Vec::with_capacity(tri.len());
-        let mut head = vec![vec![0; 0]; pos.len()];
-
-        tri.iter().enumerate().for_each(|(i, &(v1, v2, v3))| {
-            let qk = Self::cal_q(&pos[v1], &pos[v2], &pos[v3]);
-            qp.push(qk);
-            q[v1] += qk;
-            q[v2] += qk;
-            q[v3] += qk;
-            head[v1].push(i);
-            head[v2].push(i);
-            head[v3].push(i);
-        });
-
-        let mut dele = vec![false; pos.len()];
-        let mut edge = vec![];
-        let mut heap = BinaryHeap::new();
+    pub fn has_normals(&self) -> bool {
+        !self.nrm_tri.is_empty() && self.nrm_tri.len() == self.tri.len()
+    }
 
-        macro_rules! test_edge {
-            ($v1:expr, $v2:expr) => {
-                let e = pos[$v1] - pos[$v2];
-                if e.len2() >= DIST_EPS {
-                    return;
-                }
-                let qv = q[$v1] + q[$v2];
-                let inv = qv.split().inverse();
-                let v = match inv {
-                    Some(inv) => Vct::new(inv[0][3], inv[1][3], inv[2][3]),
-                    None => (pos[$v1] + pos[$v2]) * 0.5,
-                };
-                let cost = qv.multiply_by_vct(v);
-                if cost >= COST_EPS {
-                    return;
-                }
-                edge.push(Edge::new($v1, $v2, qv, v, cost));
-                heap.push(State::new(cost, edge.len() - 1));
-            };
-        }
+    pub fn has_colors(&self) -> bool {
+        !self.color.is_empty() && self.color.len() == self.pos.len()
+    }
 
-        macro_rules! in_tri {
-            ($i:expr, $v:expr) => {
-                tri[$i].0 == $v || tri[$i].1 == $v || tri[$i].2 == $v
-            };
-        }
+    pub fn has_skin(&self) -> bool {
+        !self.skin.is_empty() && self.skin.len() == self.pos.len()
+    }
 
-        macro_rules! is_valid_tri {
-            ($i:expr) => {
-                !(dele[tri[$i].0] || dele[tri[$i].1] || dele[tri[$i].2])
-            };
+    // 换成另一种精度，蒙皮权重本身就是 f64，保持不变
+    pub fn cast<U: Float>(&self) -> Mesh<U> {
+        let cast = |v: &Vec<Vct<T>>| v.iter().map(|x| x.cast()).collect();
+        Mesh {
+            pos: cast(&self.pos),
+            tri: self.tri.clone(),
+            nrm: cast(&self.nrm),
+            nrm_tri: self.nrm_tri.clone(),
+            skin: self.skin.clone(),
+            color: cast(&self.color),
         }
+    }
 
-        tri.iter().for_each(|&(v1, v2, v3)| {
-            test_edge!(v1, v2);
-            test_edge!(v2, v3);
-            test_edge!(v1, v3);
+    // 位置按点变换，法向用余子式矩阵变换后单位化；镜像变换时翻转面的朝向
+    pub fn transform(&mut self, m: &Mat<T>) {
+        self.pos.iter_mut().for_each(|p| *p = m.transform_point(*p));
+        let n = m.normal_matrix();
+        // 余子式矩阵的第一行与原矩阵第一行的点积即 3x3 部分的行列式
+        let mirror = n[0][0] * m[0][0] + n[0][1] * m[0][1] + n[0][2] * m[0][2] < T::ZERO;
+        self.nrm.iter_mut().for_each(|x| {
+            let v = n.transform_vector(*x) * if mirror { -T::ONE } else { T::ONE };
+            *x = if v.len2() > T::ZERO { v.norm() } else { v };
         });
+        if mirror {
+            self.tri.iter_mut().for_each(|t| *t = (t.0, t.2, t.1));
+            self.nrm_tri.iter_mut().for_each(|t| *t = (t.0, t.2, t.1));
+        }
+    }
 
-        let mut limit = (self.tri.len() as Flt * (1.0 - ratio)) as i64;
-        // 一次缩掉两个面
-        while let Some(state) = heap.pop() {
-            let e = edge[state.index];
-            if dele[e.v1] || dele[e.v2] {
-                continue;
+    pub fn edge_faces(&self) -> HashMap<(usize, usize), Vec<usize>> {
+        let mut edge: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        self.tri.iter().enumerate().for_each(|(i, &(a, b, c))| {
+            for &(u, v) in &[(a, b), (b, c), (c, a)] {
+                edge.entry((u.min(v), u.max(v))).or_default().push(i);
             }
-            let mut head_v = vec![];
-            let mut edge_v = vec![];
-            q.push(Mat::default());
-            pos.push(e.v);
-            dele.push(false);
-            let v = pos.len() - 1;
+        });
+        edge
+    }
 
-            // 只有一个顶点是v1的三角形
-            macro_rules! type_a {
-                ($i:expr, $v1:expr) => {
-                    if tri[$i].1 == $v1 {
-                        tri[$i] = (tri[$i].1, tri[$i].2, tri[$i].0);
-                    } else if tri[$i].2 == $v1 {
-                        tri[$i] = (tri[$i].2, tri[$i].0, tri[$i].1);
-                    }
-                    let (v2, v3) = (tri[$i].1, tri[$i].2);
-                    let qk = Self::cal_q(&pos[v], &pos[v2], &pos[v3]);
-                    let dq = qk - qp[$i];
-                    head_v.push($i);
-                    q[v] += qk;
-                    q[v2] += dq;
-                    q[v3] += dq;
-                    qp[$i] = qk;
-                    tri[$i].0 = v;
-                    edge_v.push(v2);
-                    edge_v.push(v3);
-                };
-            }
+    pub fn load(path: &str) -> Self {
+        Self::load_indexed(path).0
+    }
 
-            // 有两个顶点是v1和v2的三角形
-            macro_rules! type_b {
-                ($i:expr, $v1:expr, $v2:expr) => {
-                    let v3 = tri[$i].0 + tri[$i].1 + tri[$i].2 - $v1 - $v2;
-                    q[v3] -= qp[$i];
-                    edge_v.push(v3);
-                };
-            }
+    pub fn load_indexed(path: &str) -> (Self, VertexSource) {
+        Self::try_load_indexed(path).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-            head[e.v1].iter().for_each(|&i| {
-                if is_valid_tri!(i) {
-                    if !in_tri!(i, e.v2) {
-                        type_a!(i, e.v1);
-                    } else {
-                        type_b!(i, e.v1, e.v2);
-                    }
-                }
-            });
-            head[e.v2].iter().for_each(|&i| {
-                if is_valid_tri!(i) && !in_tri!(i, e.v1) {
-                    type_a!(i, e.v2);
-                }
-            });
-            dele[e.v1] = true;
-            dele[e.v2] = true;
-            edge_v.iter().for_each(|&vi| {
-                if !dele[vi] {
-                    test_edge!(v, vi);
-                    dele[vi] = true;
-                }
-            });
-            edge_v.iter().for_each(|&vi| {
-                dele[vi] = false;
-            });
-            head.push(head_v);
-            limit -= 2;
-            if limit < 0 {
-                break;
+    // 文件无法读取或格式不对时返回错误而不是 panic
+    pub fn try_load_indexed(path: &str) -> Result<(Self, VertexSource), String> {
+        println!("Loading the object from {}", path);
+        if Format::detect(path) == Format::Ply {
+            let (mut pos, mut tri, mut color) = (vec![], vec![], vec![]);
+            Self::try_stream(
+                path,
+                |p, c| {
+                    pos.push(p);
+                    color.extend(c);
+                },
+                |t| tri.push(t),
+            )?;
+            if tri.iter().any(|&(a, b, c)| a.max(b).max(c) >= pos.len()) {
+                return Err(format!("{}: a face refers to a missing vertex", path));
             }
-        }
-
-        let (mut new_pos, mut new_tri) = (vec![], vec![]);
-        let mut cnt = 0;
-        let mut id = vec![-1 as i64; pos.len()];
-        macro_rules! gg {
-            ($i:expr) => {{
-                if id[$i] == -1 {
-                    id[$i] = cnt;
-                    new_pos.push(pos[$i]);
-                    cnt += 1;
-                }
-                id[$i] as usize
-            }};
-        }
-        for i in 0..tri.len() {
-            if is_valid_tri!(i) {
-                new_tri.push((gg!(tri[i].0), gg!(tri[i].1), gg!(tri[i].2)));
+            println!("...Loaded");
+            let source = VertexSource { src: (0..pos.len()).collect(), count: pos.len() };
+            let mut ret = Self::from_raw(pos, tri);
+            if ret.pos.len() == color.len() {
+                ret.color = color;
             }
+            return Ok((ret, source));
         }
-        Self { pos: new_pos, tri: new_tri }
-    }
-
-    fn load(path: &str) -> (Vec<Vct>, Vec<(usize, usize, usize)>) {
-        println!("Loading the object from {}", path);
-        let file = File::open(path).expect(&format!("Cannot open {}", path));
-        let (mut t_v, mut t_f) = (vec![], vec![]);
-        for line in BufReader::new(file).lines() {
-            let line = line.expect("Failed to load the mesh object");
+        let file = File::open(path).map_err(|_| format!("Cannot open {}", path))?;
+        let (mut t_v, mut t_n, mut t_f, mut t_c) = (vec![], vec![], vec![], vec![]);
+        for (k, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|_| format!("{}: Failed to load the mesh object", path))?;
+            let err = |msg: &str| format!("{}:{}: {}", path, k + 1, msg);
             let mut w = line.split_whitespace();
             macro_rules! nx {
                 () => {
-                    w.next().unwrap().parse().unwrap()
+                    w.next().and_then(|x| x.parse().ok()).ok_or_else(|| err("invalid number"))?
                 };
             }
+            // v, v/vt, v//vn, v/vt/vn，缺省的下标记为 0
             macro_rules! nxtf {
                 () => {{
-                    let mut a = Vec::new();
-                    w.next().unwrap().split('/').for_each(|x| {
-                        if let Ok(i) = x.parse::<usize>() {
-                            a.push(i);
+                    let mut a = [0; 3];
+                    let x = w.next().ok_or_else(|| err("The mesh object has a non-triangle"))?;
+                    x.split('/').enumerate().for_each(|(i, x)| {
+                        if i < 3 {
+                            a[i] = x.parse::<usize>().unwrap_or(0);
                         }
                     });
-                    match a.len() {
-                        1 => (a[0], 0, 0),
-                        2 => (a[0], 0, a[1]),
-                        3 => (a[0], a[1], a[2]),
-                        _ => panic!("invalid vertex of a face"),
+                    if a[0] == 0 {
+                        return Err(err("invalid vertex of a face"));
                     }
+                    (a[0], a[1], a[2])
                 }};
             }
             macro_rules! wp {
                 ($e:expr) => {{
                     $e;
-                    w.next().map(|_| panic!("The mesh object has a non-triangle"));
+                    if w.next().is_some() {
+                        return Err(err("The mesh object has a non-triangle"));
+                    }
                 }};
             }
             match w.next() {
-                Some("v") => wp!(t_v.push(Vct::new(nx!(), nx!(), nx!()))),
+                Some("v") => {
+                    t_v.push(Vct::new(nx!(), nx!(), nx!()));
+                    // 扩展格式 v x y z r g b
+                    let c = w.map(|x| x.parse()).collect::<Result<Vec<T>, _>>();
+                    let c = c.map_err(|_| err("invalid number"))?;
+                    if c.len() >= 3 {
+                        t_c.push(Vct::new(c[0], c[1], c[2]));
+                    }
+                },
+                Some("vn") => t_n.push(Vct::new(nx!(), nx!(), nx!())),
                 Some("f") => wp!(t_f.push((nxtf!(), nxtf!(), nxtf!()))),
                 _ => (),
             }
         }
+        if t_f.iter().any(|&(a, b, c)| a.0.max(b.0).max(c.0) > t_v.len()) {
+            return Err(format!("{}: a face refers to a missing vertex", path));
+        }
+        // 按 (v, vt, vn) 合并面的顶点：纹理或法向不同的同一位置拆成不同的顶点，不被面引用的顶点不读入
         let mut vis = HashMap::new();
-        let (mut pos, mut tri) = (vec![], vec![]);
+        let mut src = vec![];
         macro_rules! gg {
             ($a:expr) => {{
                 *vis.entry($a).or_insert_with(|| {
-                    pos.push(t_v[$a.0 - 1]);
-                    pos.len() - 1
+                    src.push($a.0 - 1);
+                    src.len() - 1
                 })
             }};
         }
-        t_f.iter().for_each(|&(a, b, c)| {
-            let g = (gg!(a), gg!(b), gg!(c));
-            tri.push(g);
-        });
+        let tri = t_f.iter().map(|&(a, b, c)| (gg!(a), gg!(b), gg!(c))).collect();
+        let pos = src.iter().map(|&v| t_v[v]).collect();
+        let color = match t_c.len() == t_v.len() {
+            true => src.iter().map(|&v| t_c[v]).collect(),
+            false => vec![],
+        };
+        let (mut nrm, mut nrm_tri) = (vec![], vec![]);
+        let has_nrm = |x: usize| x > 0 && x <= t_n.len();
+        if !t_n.is_empty()
+            && t_f.iter().all(|&(a, b, c)| has_nrm(a.2) && has_nrm(b.2) && has_nrm(c.2))
+        {
+            nrm_tri = t_f.iter().map(|&(a, b, c)| (a.2 - 1, b.2 - 1, c.2 - 1)).collect();
+            nrm = t_n;
+        }
         println!("...Loaded");
-        (pos, tri)
+        let source = VertexSource { src, count: t_v.len() };
+        Ok((Self { pos, tri, nrm, nrm_tri, skin: vec![], color }, source))
     }
 
+    // 按扩展名选择格式，无法识别时保存为 OBJ
     pub fn save(&self, path: &str) {
+        self.save_as(path, Format::from_ext(path).unwrap_or(Format::Obj));
+    }
+
+    pub fn save_as(&self, path: &str, format: Format) {
+        self.try_save_as(path, format).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_save_as(&self, path: &str, format: Format) -> Result<(), String> {
         println!("Saving the object to {}", path);
-        let mut file = File::create(path).expect(&format!("Cannot open {}", path));
+        let mut file = File::create(path).map_err(|_| format!("Cannot open {}", path))?;
+        let s = match format {
+            Format::Obj => self.to_obj(),
+            Format::Ply => self.to_ply(),
+        };
+        write!(file, "{}", s).map_err(|_| format!("Cannot write to {}", path))?;
+        println!("...Saved");
+        Ok(())
+    }
+
+    fn to_obj(&self) -> String {
         let mut s = String::new();
-        self.pos.iter().for_each(|p| {
-            s += &format!("v {} {} {}\n", p.x, p.y, p.z);
+        self.pos.iter().enumerate().for_each(|(i, p)| match self.color.get(i) {
+            Some(c) if self.has_colors() => {
+                s += &format!("v {} {} {} {} {} {}\n", p.x, p.y, p.z, c.x, c.y, c.z)
+            },
+            _ => s += &format!("v {} {} {}\n", p.x, p.y, p.z),
         });
-        self.tri.iter().for_each(|&(a, b, c)| {
-            s += &format!("f {} {} {}\n", a + 1, b + 1, c + 1);
+        if self.has_normals() {
+            self.nrm.iter().for_each(|n| {
+                s += &format!("vn {} {} {}\n", n.x, n.y, n.z);
+            });
+            self.tri.iter().zip(self.nrm_tri.iter()).for_each(|(&(a, b, c), &(x, y, z))| {
+                s += &format!("f {}//{} {}//{} {}//{}\n", a + 1, x + 1, b + 1, y + 1, c + 1, z + 1);
+            });
+        } else {
+            self.tri.iter().for_each(|&(a, b, c)| {
+                s += &format!("f {} {} {}\n", a + 1, b + 1, c + 1);
+            });
+        }
+        s
+    }
+
+    // PLY 的法向是逐顶点的，所以分裂的法向需要把顶点按 (位置, 法向) 拆开
+    fn to_ply(&self) -> String {
+        let (mut vert, mut face) = (vec![], vec![]);
+        if self.has_normals() {
+            let mut vis = HashMap::new();
+            macro_rules! gg {
+                ($v:expr, $n:expr) => {{
+                    *vis.entry(($v, $n)).or_insert_with(|| {
+                        vert.push(($v, Some(self.nrm[$n])));
+                        vert.len() - 1
+                    })
+                }};
+            }
+            self.tri.iter().zip(self.nrm_tri.iter()).for_each(|(&(a, b, c), &(x, y, z))| {
+                face.push((gg!(a, x), gg!(b, y), gg!(c, z)));
+            });
+        } else {
+            vert = (0..self.pos.len()).map(|v| (v, None)).collect();
+            face = self.tri.clone();
+        }
+        let mut s = format!("ply\nformat ascii 1.0\nelement vertex {}\n", vert.len());
+        s += "property float x\nproperty float y\nproperty float z\n";
+        if self.has_normals() {
+            s += "property float nx\nproperty float ny\nproperty float nz\n";
+        }
+        if self.has_colors() {
+            s += "property uchar red\nproperty uchar green\nproperty uchar blue\n";
+        }
+        s += &format!("element face {}\n", face.len());
+        s += "property list uchar int vertex_indices\nend_header\n";
+        vert.iter().for_each(|&(v, n)| {
+            let p = self.pos[v];
+            s += &format!("{} {} {}", p.x, p.y, p.z);
+            if let Some(n) = n {
+                s += &format!(" {} {} {}", n.x, n.y, n.z);
+            }
+            if self.has_colors() {
+                let c = |x: T| (x.to_f64().clamp(0.0, 1.0) * 255.0).round() as u8;
+                let col = self.color[v];
+                s += &format!(" {} {} {}", c(col.x), c(col.y), c(col.z));
+            }
+            s += "\n";
         });
-        write!(file, "{}", s).expect(&format!("Cannot write to file"));
-        println!("...Saved");
+        face.iter().for_each(|&(a, b, c)| {
+            s += &format!("3 {} {} {}\n", a, b, c);
+        });
+        s
+    }
+
+    // 依次读出顶点（位置与可选的颜色）和三角形而不保存整个网格，支持 OBJ 与 PLY（ascii 和二进制）
+    pub fn stream(path: &str, vertex: impl FnMut(Vct<T>, Option<Vct<T>>), face: impl FnMut(Tri)) {
+        Self::try_stream(path, vertex, face).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_stream(
+        path: &str,
+        mut vertex: impl FnMut(Vct<T>, Option<Vct<T>>),
+        mut face: impl FnMut(Tri),
+    ) -> Result<(), String> {
+        let file = File::open(path).map_err(|_| format!("Cannot open {}", path))?;
+        let mut r = BufReader::new(file);
+        let fail = |msg: &str| format!("{}: {}", path, msg);
+        if Format::detect(path) == Format::Obj {
+            for (k, line) in r.lines().enumerate() {
+                let line = line.map_err(|_| fail("Failed to load the mesh object"))?;
+                let err = |msg: &str| format!("{}:{}: {}", path, k + 1, msg);
+                let mut w = line.split_whitespace();
+                macro_rules! nx {
+                    () => {
+                        w.next()
+                            .and_then(|x| x.split('/').next().unwrap().parse().ok())
+                            .ok_or_else(|| err("invalid number"))?
+                    };
+                }
+                match w.next() {
+                    Some("v") => {
+                        let p = Vct::new(nx!(), nx!(), nx!());
+                        let c = w.map(|x| x.parse()).collect::<Result<Vec<T>, _>>();
+                        let c = c.map_err(|_| err("invalid number"))?;
+                        vertex(
+                            p,
+                            Some(c).filter(|c| c.len() >= 3).map(|c| Vct::new(c[0], c[1], c[2])),
+                        );
+                    },
+                    Some("f") => {
+                        let t: (usize, usize, usize) = (nx!(), nx!(), nx!());
+                        if w.next().is_some() {
+                            return Err(err("The mesh object has a non-triangle"));
+                        }
+                        if t.0 == 0 || t.1 == 0 || t.2 == 0 {
+                            return Err(err("invalid vertex of a face"));
+                        }
+                        face((t.0 - 1, t.1 - 1, t.2 - 1));
+                    },
+                    _ => (),
+                }
+            }
+            return Ok(());
+        }
+
+        // (元素名, 个数, [(属性名, 类型, 列表长度的类型)])
+        type Prop = (String, String, Option<String>);
+        let mut elements: Vec<(String, usize, Vec<Prop>)> = vec![];
+        let mut format = String::new();
+        loop {
+            let mut line = String::new();
+            if r.read_line(&mut line).map_err(|_| fail("Failed to load the mesh object"))? == 0 {
+                return Err(fail("Unexpected end of PLY header"));
+            }
+            let w: Vec<_> = line.split_whitespace().collect();
+            match w.as_slice() {
+                ["format", f, ..] => format = f.to_string(),
+                ["element", name, n] => {
+                    let n = n.parse().map_err(|_| fail("Invalid element count in PLY header"))?;
+                    elements.push((name.to_string(), n, vec![]))
+                },
+                ["property", ..] if elements.is_empty() => {
+                    return Err(fail("PLY property before any element"))
+                },
+                ["property", "list", n, t, name] => elements.last_mut().unwrap().2.push((
+                    name.to_string(),
+                    t.to_string(),
+                    Some(n.to_string()),
+                )),
+                ["property", t, name] => {
+                    elements.last_mut().unwrap().2.push((name.to_string(), t.to_string(), None))
+                },
+                ["end_header"] => break,
+                _ => (),
+            }
+        }
+        let mut tokens: Vec<String> = vec![];
+        let mut nx = |ty: &str| -> Result<Flt, String> {
+            if format == "ascii" {
+                while tokens.is_empty() {
+                    let mut line = String::new();
+                    if r.read_line(&mut line).map_err(|_| fail("Failed to load the mesh object"))?
+                        == 0
+                    {
+                        return Err(fail("Unexpected end of PLY file"));
+                    }
+                    tokens = line.split_whitespace().rev().map(String::from).collect();
+                }
+                return tokens
+                    .pop()
+                    .unwrap()
+                    .parse()
+                    .map_err(|_| fail("Invalid number in PLY file"));
+            }
+            let size = match ty {
+                "char" | "uchar" | "int8" | "uint8" => 1,
+                "short" | "ushort" | "int16" | "uint16" => 2,
+                "int" | "uint" | "float" | "int32" | "uint32" | "float32" => 4,
+                "double" | "float64" => 8,
+                _ => return Err(fail(&format!("Unknown PLY type {}", ty))),
+            };
+            let mut b = [0u8; 8];
+            r.read_exact(&mut b[..size]).map_err(|_| fail("Unexpected end of PLY file"))?;
+            if format == "binary_big_endian" {
+                b[..size].reverse();
+            }
+            let (b1, b2, b4) = ([b[0]], [b[0], b[1]], [b[0], b[1], b[2], b[3]]);
+            Ok(match ty {
+                "char" | "int8" => i8::from_le_bytes(b1) as Flt,
+                "uchar" | "uint8" => u8::from_le_bytes(b1) as Flt,
+                "short" | "int16" => i16::from_le_bytes(b2) as Flt,
+                "ushort" | "uint16" => u16::from_le_bytes(b2) as Flt,
+                "int" | "int32" => i32::from_le_bytes(b4) as Flt,
+                "uint" | "uint32" => u32::from_le_bytes(b4) as Flt,
+                "float" | "float32" => f32::from_le_bytes(b4) as Flt,
+                _ => f64::from_le_bytes(b) as Flt,
+            })
+        };
+        for (name, n, props) in elements.iter() {
+            for _ in 0..*n {
+                let (mut p, mut idx, mut c) = ([0.0; 3], vec![], [None; 3]);
+                for (prop, ty, list) in props.iter() {
+                    match list {
+                        Some(lt) => {
+                            let len = nx(lt)? as usize;
+                            let list = (0..len)
+                                .map(|_| nx(ty).map(|x| x as usize))
+                                .collect::<Result<Vec<_>, _>>()?;
+                            if prop.starts_with("vertex_ind") {
+                                idx = list;
+                            }
+                        },
+                        None => {
+                            let x = nx(ty)?;
+                            match prop.as_str() {
+                                "x" => p[0] = x,
+                                "y" => p[1] = x,
+                                "z" => p[2] = x,
+                                // 整数类型的颜色在 [0, 255] 内
+                                "red" | "green" | "blue" => {
+                                    let x = if ty.contains("float") || ty == "double" {
+                                        x
+                                    } else {
+                                        x / 255.0
+                                    };
+                                    c[match prop.as_str() {
+                                        "red" => 0,
+                                        "green" => 1,
+                                        _ => 2,
+                                    }] = Some(x);
+                                },
+                                _ => (),
+                            }
+                        },
+                    }
+                }
+                match name.as_str() {
+                    "vertex" => vertex(
+                        Vct::new(p[0], p[1], p[2]).cast(),
+                        match c {
+                            [Some(r), Some(g), Some(b)] => Some(Vct::new(r, g, b).cast()),
+                            _ => None,
+                        },
+                    ),
+                    "face" if idx.len() == 3 => face((idx[0], idx[1], idx[2])),
+                    "face" => return Err(fail("The mesh object has a non-triangle")),
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NormalWeight, EPS, PI};
+
+    #[test]
+    fn load_obj() {
+        // 第 5 个顶点没有被引用，第 1 个顶点在两个面上的法向不同
+        let path = std::env::temp_dir().join("mesh_simplification_load.obj");
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nv 9 9 9\nvn 0 0 1\nvn 0 1 0\n\
+                 f 1//1 2//1 3//1\nf 1//2 4//2 2//2\n";
+        std::fs::write(&path, s).unwrap();
+        let (mesh, source) = Mesh::load_indexed(path.to_str().unwrap());
+        assert_eq!(mesh.tri, vec![(0, 1, 2), (3, 4, 5)]);
+        assert_eq!(source, VertexSource { src: vec![0, 1, 2, 0, 3, 1], count: 5 });
+        assert_eq!((mesh.pos[3], mesh.pos[4]), (Vct::zero(), Vct::new(0.0, 0.0, 1.0)));
+        assert_eq!(mesh.nrm_tri, vec![(0, 0, 0), (1, 1, 1)]);
+        assert!(mesh.to_obj().contains("vn 0 1 0\nf 1//1 2//1 3//1\n"));
+        // 法向与面不对应时不写 vn
+        let mut stale = mesh.clone();
+        stale.nrm_tri.clear();
+        assert!(!stale.to_obj().contains("vn") && stale.to_obj().contains("f 1 2 3\n"));
+        // 格式错误时返回带行号的信息
+        std::fs::write(&path, "v 0 0 0\nv 1 0 x\nf 1 2 3\n").unwrap();
+        let e = Mesh::<Flt>::try_load_indexed(path.to_str().unwrap()).unwrap_err();
+        assert!(e.ends_with(":2: invalid number"), "{}", e);
+        std::fs::write(&path, "v 0 0 0\nf 1 2 3\n").unwrap();
+        assert!(Mesh::<Flt>::try_load_indexed(path.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn binary_ply() {
+        let mesh = Mesh::new("assets/cube.obj");
+        let path = std::env::temp_dir().join("mesh_simplification_cube.ply");
+        let mut file = File::create(&path).unwrap();
+        let header = format!(
+            "ply\nformat binary_little_endian 1.0\nelement vertex {}\nproperty float x\n\
+             property float y\nproperty float z\nproperty uchar red\nelement face {}\n\
+             property list uchar int vertex_indices\nend_header\n",
+            mesh.pos.len(),
+            mesh.tri.len()
+        );
+        file.write_all(header.as_bytes()).unwrap();
+        for p in mesh.pos.iter() {
+            for x in [p.x, p.y, p.z] {
+                file.write_all(&(x as f32).to_le_bytes()).unwrap();
+            }
+            file.write_all(&[255]).unwrap();
+        }
+        for &(a, b, c) in mesh.tri.iter() {
+            file.write_all(&[3]).unwrap();
+            for v in [a, b, c] {
+                file.write_all(&(v as i32).to_le_bytes()).unwrap();
+            }
+        }
+        drop(file);
+        let ply = Mesh::new(path.to_str().unwrap());
+        assert_eq!(ply.pos, mesh.pos);
+        assert_eq!(ply.tri, mesh.tri);
+    }
+
+    #[test]
+    fn transform() {
+        let mut mesh = Mesh::new("assets/cube.obj");
+        mesh.compute_normals(NormalWeight::Angle, None);
+        let m =
+            Mat::rotate(Vct::new(1.0, 1.0, 0.0), PI / 3.0) * Mat::scale(Vct::new(2.0, 1.0, -0.5));
+        let mut moved = mesh.clone();
+        moved.transform(&m);
+        assert_ne!(moved.tri, mesh.tri);
+        // 变换后的法向与重新计算的一致，仍然朝外
+        let nrm = moved.vertex_normals(NormalWeight::Angle);
+        moved.nrm.iter().zip(nrm.iter()).for_each(|(a, b)| assert!(a.dot(*b) > 0.5));
+        let center = m.transform_point(Vct::one() * 0.5);
+        moved.nrm.iter().zip(moved.pos.iter()).for_each(|(n, p)| assert!(n.dot(*p - center) > 0.0));
+        moved.transform(&m.inverse().unwrap());
+        assert_eq!(moved.tri, mesh.tri);
+        moved.pos.iter().zip(mesh.pos.iter()).for_each(|(a, b)| assert!(a.distance(*b) < EPS));
+        moved.nrm.iter().zip(mesh.nrm.iter()).for_each(|(a, b)| assert!(a.distance(*b) < EPS));
     }
 }
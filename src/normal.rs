@@ -0,0 +1,191 @@
+use crate::float::Float;
+use crate::mesh::Tri;
+use crate::{Flt, Mesh, Vct, PI};
+use std::fmt;
+use std::str::FromStr;
+
+// 简化与聚类的输入带法向时，按这个折痕角（角度制）为结果重新计算法向
+pub(crate) const NORMAL_CREASE: Flt = 60.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalWeight {
+    Uniform,
+    Area,
+    Angle,
+}
+
+impl fmt::Display for NormalWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            NormalWeight::Uniform => "uniform",
+            NormalWeight::Area => "area",
+            NormalWeight::Angle => "angle",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for NormalWeight {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "uniform" => Ok(NormalWeight::Uniform),
+            "area" => Ok(NormalWeight::Area),
+            "angle" => Ok(NormalWeight::Angle),
+            _ => Err("uniform, area or angle".to_string()),
+        }
+    }
+}
+
+fn unit<T: Float>(v: Vct<T>) -> Vct<T> {
+    let len = v.len();
+    if len > T::ZERO {
+        v / len
+    } else {
+        Vct::zero()
+    }
+}
+
+pub(crate) fn angle<T: Float>(a: Vct<T>, b: Vct<T>) -> T {
+    let (a, b) = (unit(a), unit(b));
+    a.dot(b).clamp(-T::ONE, T::ONE).acos()
+}
+
+pub(crate) fn find(f: &mut [usize], x: usize) -> usize {
+    let mut r = x;
+    while f[r] != r {
+        r = f[r];
+    }
+    let mut x = x;
+    while f[x] != r {
+        let t = f[x];
+        f[x] = r;
+        x = t;
+    }
+    r
+}
+
+impl<T: Float> Mesh<T> {
+    fn corner(&self, i: usize, k: usize) -> usize {
+        let (a, b, c) = self.tri[i];
+        [a, b, c][k]
+    }
+
+    // 第 i 个面在第 k 个角上对顶点法向的贡献
+    fn corner_normal(&self, i: usize, k: usize, weight: NormalWeight) -> Vct<T> {
+        let p = self.corner(i, k);
+        let (a, b) = (self.corner(i, (k + 1) % 3), self.corner(i, (k + 2) % 3));
+        let (e1, e2) = (self.pos[a] - self.pos[p], self.pos[b] - self.pos[p]);
+        let n = e1 % e2;
+        match weight {
+            NormalWeight::Uniform => unit(n),
+            NormalWeight::Area => n * T::from_f64(0.5),
+            NormalWeight::Angle => unit(n) * angle(e1, e2),
+        }
+    }
+
+    pub fn face_normals(&self) -> Vec<Vct<T>> {
+        self.tri
+            .iter()
+            .map(|&(a, b, c)| unit((self.pos[b] - self.pos[a]) % (self.pos[c] - self.pos[a])))
+            .collect()
+    }
+
+    pub fn vertex_normals(&self, weight: NormalWeight) -> Vec<Vct<T>> {
+        let mut nrm = vec![Vct::zero(); self.pos.len()];
+        for i in 0..self.tri.len() {
+            for k in 0..3 {
+                nrm[self.corner(i, k)] += self.corner_normal(i, k, weight);
+            }
+        }
+        nrm.into_iter().map(unit).collect()
+    }
+
+    // 二面角大于 crease（角度制）的边两侧不共享法向
+    pub fn split_normals(&self, weight: NormalWeight, crease: Flt) -> (Vec<Vct<T>>, Vec<Tri>) {
+        let fnrm = self.face_normals();
+        let limit = T::from_f64(crease * PI / 180.0);
+        let edge = self.edge_faces();
+        let mut f: Vec<usize> = (0..self.tri.len() * 3).collect();
+        let slot = |i: usize, v: usize| (0..3).find(|&k| self.corner(i, k) == v).unwrap() + i * 3;
+        for (&(u, v), faces) in edge.iter() {
+            for x in 0..faces.len() {
+                for y in x + 1..faces.len() {
+                    let (i, j) = (faces[x], faces[y]);
+                    if angle(fnrm[i], fnrm[j]) <= limit {
+                        for &w in &[u, v] {
+                            let (p, q) = (find(&mut f, slot(i, w)), find(&mut f, slot(j, w)));
+                            f[p] = q;
+                        }
+                    }
+                }
+            }
+        }
+        let mut id = vec![usize::MAX; f.len()];
+        let mut nrm = vec![];
+        for s in 0..f.len() {
+            let r = find(&mut f, s);
+            if id[r] == usize::MAX {
+                id[r] = nrm.len();
+                nrm.push(Vct::zero());
+            }
+            nrm[id[r]] += self.corner_normal(s / 3, s % 3, weight);
+        }
+        let nrm = nrm.into_iter().map(unit).collect();
+        let nrm_tri = (0..self.tri.len())
+            .map(|i| {
+                (id[find(&mut f, i * 3)], id[find(&mut f, i * 3 + 1)], id[find(&mut f, i * 3 + 2)])
+            })
+            .collect();
+        (nrm, nrm_tri)
+    }
+
+    // crease 为 None 时每个顶点只有一个法向
+    pub fn compute_normals(&mut self, weight: NormalWeight, crease: Option<Flt>) {
+        let (nrm, nrm_tri) = match crease {
+            Some(crease) => self.split_normals(weight, crease),
+            None => (self.vertex_normals(weight), self.tri.clone()),
+        };
+        self.nrm = nrm;
+        self.nrm_tri = nrm_tri;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EPS;
+
+    fn cube() -> Mesh {
+        Mesh::new("assets/cube.obj")
+    }
+
+    #[test]
+    fn face_normals() {
+        let mesh = cube();
+        mesh.face_normals().iter().zip(mesh.tri.iter()).for_each(|(n, &(a, b, c))| {
+            let center = (mesh.pos[a] + mesh.pos[b] + mesh.pos[c]) / 3.0;
+            assert!((n.len() - 1.0).abs() < EPS);
+            assert!(n.dot(center - Vct::one() * 0.5) > 0.0);
+        });
+    }
+
+    #[test]
+    fn vertex_normals() {
+        let mesh = cube();
+        mesh.vertex_normals(NormalWeight::Angle).iter().zip(mesh.pos.iter()).for_each(|(n, p)| {
+            let d = unit(*p - Vct::one() * 0.5);
+            assert!((n.dot(d) - 1.0).abs() < EPS);
+        });
+    }
+
+    #[test]
+    fn split_normals() {
+        let mut mesh = cube();
+        mesh.compute_normals(NormalWeight::Angle, Some(60.0));
+        assert_eq!(mesh.nrm.len(), 24);
+        assert!(mesh.has_normals());
+        mesh.compute_normals(NormalWeight::Angle, Some(120.0));
+        assert_eq!(mesh.nrm.len(), 8);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::cluster::Grid;
+use crate::progress::{Control, Observer, Progress};
+use crate::{Aabb, Flt, Mesh, NormalWeight, Vct};
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// 每页的顶点数与缓存的页数，缓存最多占 PAGE * PAGES * 24 字节
+const PAGE: usize = 4096;
+const PAGES: usize = 256;
+// 开始、结束时以及每处理这么多个三角形通知一次 observer
+const INTERVAL: usize = 100000;
+
+// 第一遍读入的顶点以 f64 写入临时文件，第二遍按下标读回，只在内存中缓存少量的页；
+// 不转换成 f32，离原点很远的坐标（如带地理坐标的扫描数据）也不会损失精度
+struct VertexFile {
+    path: PathBuf,
+    file: File,
+    len: usize,
+    // 直接映射的页缓存：第 k 页放在 k % PAGES 处
+    cache: Vec<Option<(usize, Vec<Vct>)>>,
+}
+
+impl VertexFile {
+    fn create() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "mesh_simplification_{}_{}.vert",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap_or_else(|_| panic!("Cannot create {}", path.display()));
+        Self { path, file, len: 0, cache: vec![None; PAGES] }
+    }
+
+    fn get(&mut self, v: usize) -> Vct {
+        assert!(v < self.len, "Vertex index {} out of range", v + 1);
+        let (page, slot) = (v / PAGE, v / PAGE % PAGES);
+        if self.cache[slot].as_ref().is_none_or(|(p, _)| *p != page) {
+            let n = PAGE.min(self.len - page * PAGE);
+            let mut buf = vec![0u8; n * 24];
+            self.file.seek(SeekFrom::Start((page * PAGE * 24) as u64)).unwrap();
+            self.file.read_exact(&mut buf).expect("Failed to read the vertex file");
+            let f = |i: usize| Flt::from_le_bytes(buf[i..i + 8].try_into().unwrap());
+            let pts = (0..n).map(|k| Vct::new(f(k * 24), f(k * 24 + 8), f(k * 24 + 16))).collect();
+            self.cache[slot] = Some((page, pts));
+        }
+        self.cache[slot].as_ref().unwrap().1[v % PAGE]
+    }
+}
+
+impl Drop for VertexFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Lindstrom 的 OoCS：把包围盒沿最长轴划分成 grid 格的均匀网格，流式读入三角形做顶点聚类。
+// 第一遍求包围盒并把顶点写入临时文件，第二遍逐个三角形从文件中取回顶点；
+// 内存只和缓存的大小以及被占据的格子数（即输出的规模）有关。
+// weight 为误差矩阵在三角形各个角上的加权方式，与简化时相同。
+// 第二遍中 observer 收到的 faces 为尚未处理的三角形数；输出的面数取决于被占据的格子，事先无法知道，
+// 所以 target 总是 0，faces 从输入的三角形数减到 0。取消后其余的三角形被忽略
+pub fn simplify_out_of_core(
+    path: &str,
+    grid: usize,
+    weight: NormalWeight,
+    observer: Option<&dyn Observer>,
+) -> Mesh {
+    let mut vert = VertexFile::create();
+    let (mut b, mut len, mut total) = (Aabb::empty(), 0, 0);
+    {
+        let mut w = BufWriter::new(&vert.file);
+        Mesh::<Flt>::stream(
+            path,
+            |p, _| {
+                b.add(p);
+                for x in [p.x, p.y, p.z] {
+                    w.write_all(&x.to_le_bytes()).expect("Failed to write the vertex file");
+                }
+                len += 1;
+            },
+            |_| total += 1,
+        );
+        w.flush().expect("Failed to write the vertex file");
+    }
+    vert.len = len;
+
+    // 输入为空或所有顶点重合时包围盒没有大小，此时只有一个格子
+    let side = b.longest_side();
+    let cell = if side > 0.0 { side / grid.max(1) as Flt } else { 1.0 };
+    let mut g = Grid::new(b.lo, cell, weight);
+    let mut done = 0;
+    let report = |done: usize| match observer {
+        Some(ob) => ob.progress(&Progress { faces: total - done, target: 0, error: 0.0 }),
+        None => Control::Continue,
+    };
+    let mut cancelled = report(0) == Control::Cancel;
+    Mesh::<Flt>::stream(
+        path,
+        |_, _| (),
+        |(x, y, z)| {
+            if cancelled {
+                return;
+            }
+            g.add([x, y, z].map(|v| vert.get(v)));
+            done += 1;
+            if done % INTERVAL == 0 {
+                cancelled = report(done) == Control::Cancel;
+            }
+        },
+    );
+    if !cancelled {
+        report(done);
+    }
+    g.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn out_of_core() {
+        let volume = |m: &Mesh| {
+            m.tri.iter().map(|&(a, b, c)| m.pos[a].dot(m.pos[b] % m.pos[c]) / 6.0).sum::<Flt>()
+        };
+        let mesh = Mesh::new("assets/bunny.fine.obj");
+        let calls = Mutex::new(vec![]);
+        let ob = |p: &Progress| {
+            calls.lock().unwrap().push(p.faces);
+            Control::Continue
+        };
+        let out =
+            simplify_out_of_core("assets/bunny.fine.obj", 40, NormalWeight::Uniform, Some(&ob));
+        assert!(out.tri.len() < mesh.tri.len() / 4);
+        assert!((volume(&out) - volume(&mesh)).abs() < volume(&mesh).abs() * 0.05);
+        // 每个输出的顶点都被某个三角形用到
+        let mut used = vec![false; out.pos.len()];
+        out.tri.iter().for_each(|&(a, b, c)| [a, b, c].iter().for_each(|&v| used[v] = true));
+        assert!(used.into_iter().all(|u| u));
+        assert_eq!(calls.into_inner().unwrap(), vec![mesh.tri.len(), 0]);
+        // 格子足够小时每个顶点自成一格，结果与输入相同
+        let small = simplify_out_of_core("assets/sphere.obj", 1000, NormalWeight::Area, None);
+        let mut sphere = Mesh::new("assets/sphere.obj");
+        assert_eq!(small.tri.len(), sphere.tri.len());
+
+        // 远离原点的坐标不损失精度：f32 在 1e7 附近的间隔为 1，远大于球面上顶点的间距
+        let offset = Vct::new(1e7, -2e7, 5e6);
+        sphere.pos.iter_mut().for_each(|p| *p += offset);
+        let path = std::env::temp_dir().join("mesh_simplification_offset.obj");
+        let path = path.to_str().unwrap();
+        sphere.save(path);
+        let far = simplify_out_of_core(path, 1000, NormalWeight::Uniform, None);
+        assert_eq!(far.tri.len(), sphere.tri.len());
+        let (b1, b2) = (far.bbox(), sphere.bbox());
+        assert!((b1.lo - b2.lo).len() < 1e-6 && (b1.hi - b2.hi).len() < 1e-6);
+        assert!((far.area() - sphere.area()).abs() < sphere.area() * 1e-6);
+    }
+}
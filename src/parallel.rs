@@ -0,0 +1,266 @@
+use crate::cost::{CollapseCost, Placement};
+use crate::progress::{Control, Observer, Progress};
+use crate::simplify::{Carry, Collapse};
+use crate::{Flt, Mesh, SimplifyOptions};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+// 平均每个线程至少分到这么多面时才分块并行收缩
+pub const PATCH_FACES: usize = 10000;
+
+// 把 data 切成 threads 段并行处理，f 的第一个参数是该段在 data 中的起始下标
+pub fn par_chunks_mut<T: Send, F>(data: &mut [T], threads: usize, f: F)
+where
+    F: Fn(usize, &mut [T]) + Sync,
+{
+    let size = data.len().div_ceil(threads.max(1));
+    if threads <= 1 || size == 0 {
+        f(0, data);
+        return;
+    }
+    thread::scope(|s| {
+        for (k, chunk) in data.chunks_mut(size).enumerate() {
+            let f = &f;
+            s.spawn(move || f(k * size, chunk));
+        }
+    });
+}
+
+// 把 0..n 切成 threads 段并行处理，按顺序拼接结果
+pub fn par_map<R: Send, F>(n: usize, threads: usize, f: F) -> Vec<R>
+where
+    F: Fn(Range<usize>) -> Vec<R> + Sync,
+{
+    if threads <= 1 {
+        return f(0..n);
+    }
+    let size = n.div_ceil(threads);
+    thread::scope(|s| {
+        let hs: Vec<_> = (0..threads)
+            .map(|k| {
+                let f = &f;
+                s.spawn(move || f((k * size).min(n)..((k + 1) * size).min(n)))
+            })
+            .collect();
+        hs.into_iter().flat_map(|h| h.join().unwrap()).collect()
+    })
+}
+
+// g[i] 是新网格中第 i 个顶点在原网格中的下标
+fn remap(opts: &SimplifyOptions, g: &[usize]) -> SimplifyOptions {
+    let inv: HashMap<_, _> = g.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+    SimplifyOptions {
+        weights: match opts.weights.is_empty() {
+            true => vec![],
+            false => g.iter().map(|&v| *opts.weights.get(v).unwrap_or(&1.0)).collect(),
+        },
+        locked: g.iter().map(|&v| *opts.locked.get(v).unwrap_or(&false)).collect(),
+        crease_edges: opts
+            .crease_edges
+            .iter()
+            .filter_map(|&(a, b)| Some((*inv.get(&a)?, *inv.get(&b)?)))
+            .collect(),
+        ..opts.clone()
+    }
+}
+
+impl Mesh {
+    // 取出 faces 组成的子网格，同时返回子网格顶点在原网格中的下标
+    fn extract(&self, faces: &[usize]) -> (Self, Vec<usize>) {
+        let mut id = HashMap::new();
+        let (mut pos, mut g) = (vec![], vec![]);
+        let mut gg = |v: usize| {
+            *id.entry(v).or_insert_with(|| {
+                pos.push(self.pos[v]);
+                g.push(v);
+                pos.len() - 1
+            })
+        };
+        let tri = faces
+            .iter()
+            .map(|&i| {
+                let (a, b, c) = self.tri[i];
+                (gg(a), gg(b), gg(c))
+            })
+            .collect();
+        let mut sub = Self::from_raw(pos, tri);
+        if self.has_skin() {
+            sub.skin = g.iter().map(|&v| self.skin[v]).collect();
+        }
+        if self.has_colors() {
+            sub.color = g.iter().map(|&v| self.color[v]).collect();
+        }
+        (sub, g)
+    }
+
+    // 沿包围盒最长轴把面切成 threads 块，块之间共享的顶点锁定后各块并行简化，
+    // 合并后带着各块的顶点权重与最大代价再整体简化一遍去掉接缝处多余的面
+    pub(crate) fn simplify_parallel(
+        &self,
+        opts: &SimplifyOptions,
+        cost: &dyn CollapseCost,
+        placement: &dyn Placement,
+    ) -> (Self, Vec<usize>, Vec<Collapse>) {
+        let threads = opts.threads;
+        let axis = self.bbox().longest_axis();
+        let key = |i: usize| {
+            let (a, b, c) = self.tri[i];
+            self.pos[a][axis] + self.pos[b][axis] + self.pos[c][axis]
+        };
+        let mut order: Vec<_> = (0..self.tri.len()).collect();
+        order.sort_by(|&a, &b| key(a).total_cmp(&key(b)));
+        let parts: Vec<_> = order.chunks(order.len().div_ceil(threads)).collect();
+
+        let (mut owner, mut shared) =
+            (vec![usize::MAX; self.pos.len()], vec![false; self.pos.len()]);
+        for (k, part) in parts.iter().enumerate() {
+            for &i in part.iter() {
+                let (a, b, c) = self.tri[i];
+                for v in [a, b, c] {
+                    if owner[v] == usize::MAX {
+                        owner[v] = k;
+                    } else if owner[v] != k {
+                        shared[v] = true;
+                    }
+                }
+            }
+        }
+
+        // 各块的进度汇总成整个网格的进度，任意一块被取消后所有块都停止
+        let total = self.tri.len();
+        let target = total - (total as Flt * (1.0 - opts.ratio)).max(0.0) as usize;
+        let done = Arc::new((0..parts.len()).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>());
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let results: Vec<(Self, Vec<usize>, Vec<Collapse>, Carry)> = thread::scope(|s| {
+            let hs: Vec<_> = parts
+                .iter()
+                .enumerate()
+                .map(|(k, part)| {
+                    let shared = &shared;
+                    let (done, cancelled, len) = (done.clone(), cancelled.clone(), part.len());
+                    s.spawn(move || {
+                        let (sub, g) = self.extract(part);
+                        let mut o = remap(opts, &g);
+                        o.locked.iter_mut().zip(g.iter()).for_each(|(l, &v)| *l |= shared[v]);
+                        o.threads = 1;
+                        o.observer = opts.observer.clone().map(|ob| -> Arc<dyn Observer> {
+                            Arc::new(move |p: &Progress| {
+                                done[k].store(len - p.faces, Ordering::Relaxed);
+                                let removed: usize =
+                                    done.iter().map(|d| d.load(Ordering::Relaxed)).sum();
+                                let p = Progress { faces: total - removed, target, error: p.error };
+                                if cancelled.load(Ordering::Relaxed)
+                                    || ob.progress(&p) == Control::Cancel
+                                {
+                                    cancelled.store(true, Ordering::Relaxed);
+                                    return Control::Cancel;
+                                }
+                                Control::Continue
+                            })
+                        });
+                        let (out, src, log, carry) =
+                            sub.simplify_serial(&o, cost, placement, &Carry::default());
+                        let log = log
+                            .into_iter()
+                            .map(|c| Collapse { v1: g[c.v1], v2: g[c.v2], ..c })
+                            .collect();
+                        (out, src.into_iter().map(|i| g[i]).collect(), log, carry)
+                    })
+                })
+                .collect();
+            hs.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let mut id = vec![usize::MAX; self.pos.len()];
+        let (mut pos, mut tri, mut src, mut collapses) = (vec![], vec![], vec![], vec![]);
+        let (mut skin, mut color) = (vec![], vec![]);
+        // 共享的顶点被锁定，位置在各块中相同；误差矩阵在最后一遍由合并后的面重新计算，只带上顶点权重与最大代价
+        let mut carry = Carry::default();
+        for (out, g, log, c) in results {
+            collapses.extend(log);
+            carry.error = carry.error.max(c.error);
+            let local: Vec<_> = (0..g.len())
+                .map(|k| {
+                    let v = g[k];
+                    if id[v] == usize::MAX {
+                        id[v] = pos.len();
+                        pos.push(out.pos[k]);
+                        src.push(v);
+                        skin.extend(out.skin.get(k));
+                        color.extend(out.color.get(k));
+                        carry.weights.push(c.weights[k]);
+                    }
+                    id[v]
+                })
+                .collect();
+            tri.extend(out.tri.iter().map(|&(a, b, c)| (local[a], local[b], local[c])));
+        }
+        let mut merged = Self::from_raw(pos, tri);
+        merged.skin = skin;
+        merged.color = color;
+        if cancelled.load(Ordering::Relaxed) {
+            return (merged, src, collapses);
+        }
+        let mut o = remap(opts, &src);
+        o.ratio = (self.tri.len() as Flt * opts.ratio / merged.tri.len() as Flt).min(1.0);
+        // 最后一遍从各块结束时的面数与最大代价继续，目标按整个网格计，进度不会倒退
+        o.observer = opts.observer.clone().map(|ob| -> Arc<dyn Observer> {
+            Arc::new(move |p: &Progress| ob.progress(&Progress { target, ..*p }))
+        });
+        let (out, g, log, _) = merged.simplify_serial(&o, cost, placement, &carry);
+        collapses.extend(log.into_iter().map(|c| Collapse { v1: src[c.v1], v2: src[c.v2], ..c }));
+        (out, g.into_iter().map(|i| src[i]).collect(), collapses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks() {
+        let mut a: Vec<usize> = vec![0; 1000];
+        par_chunks_mut(&mut a, 3, |start, chunk| {
+            chunk.iter_mut().enumerate().for_each(|(k, x)| *x = start + k);
+        });
+        assert!(a.iter().enumerate().all(|(i, &x)| i == x));
+        let b = par_map(1000, 7, |r| r.map(|i| i * 2).collect());
+        assert!(b.iter().enumerate().all(|(i, &x)| i * 2 == x));
+    }
+
+    #[test]
+    fn parallel() {
+        let volume = |m: &Mesh| {
+            m.tri.iter().map(|&(a, b, c)| m.pos[a].dot(m.pos[b] % m.pos[c]) / 6.0).sum::<Flt>()
+        };
+        // 两个线程时面数刚好够切成两块
+        let mesh = Mesh::new("assets/sphere.obj");
+        assert!(mesh.tri.len() >= 2 * PATCH_FACES);
+        let serial = mesh.simplify(0.05);
+        let log = Arc::new(std::sync::Mutex::new(vec![]));
+        let l = log.clone();
+        let opts = SimplifyOptions {
+            ratio: 0.05,
+            threads: 2,
+            observer: Some(Arc::new(move |p: &Progress| {
+                l.lock().unwrap().push(*p);
+                Control::Continue
+            })),
+            ..Default::default()
+        };
+        let ret = mesh.simplify_detailed(&opts);
+        let out = ret.mesh;
+        // 最后一遍的进度接着各块的进度，最大代价也不会变小
+        let log = log.lock().unwrap();
+        let last = log.last().unwrap();
+        assert_eq!(last.faces, out.tri.len());
+        assert!(log.iter().all(|p| p.target == last.target && p.error <= last.error));
+        assert!(out.tri.len() <= (mesh.tri.len() as Flt * 0.05) as usize + 2);
+        assert!(ret.vertex_map.iter().all(|&j| j < out.pos.len()));
+        assert_eq!(ret.collapses.len(), mesh.pos.len() - out.pos.len());
+        assert!((volume(&out) - volume(&serial)).abs() < volume(&mesh) * 0.01);
+    }
+}
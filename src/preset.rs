@@ -0,0 +1,180 @@
+use crate::json::Json;
+use crate::{Flt, SimplifyOptions};
+use std::fs;
+
+// 预设中可以出现的字段；逐顶点的权重、锁定与折痕边依赖具体的网格，observer 无法保存，都不在其中
+const FIELDS: &[&str] = &[
+    "ratio",
+    "max_error",
+    "feature_angle",
+    "feature_weight",
+    "preserve_boundary",
+    "strategy",
+    "placement",
+    "threads",
+    "progress_interval",
+    "skin_penalty",
+    "color_weight",
+    "quadric_weight",
+    "normalize",
+];
+
+impl SimplifyOptions {
+    pub fn to_json(&self) -> Json {
+        let opt = |x: Option<Flt>| Json::from(x);
+        let values = vec![
+            Json::from(self.ratio),
+            opt(self.max_error),
+            opt(self.feature_angle),
+            self.feature_weight.into(),
+            self.preserve_boundary.into(),
+            self.strategy.to_string().into(),
+            self.placement.to_string().into(),
+            self.threads.into(),
+            self.progress_interval.into(),
+            self.skin_penalty.into(),
+            self.color_weight.into(),
+            self.quadric_weight.to_string().into(),
+            self.normalize.into(),
+        ];
+        Json::Obj(FIELDS.iter().map(|k| k.to_string()).zip(values).collect())
+    }
+
+    // 只修改 j 中出现的字段，未知的字段与类型不对的值都是错误
+    pub fn apply_json(&mut self, j: &Json) -> Result<(), String> {
+        let fields = match j {
+            Json::Obj(o) => o,
+            _ => return Err("options must be a JSON object".to_string()),
+        };
+        for (k, v) in fields {
+            let bad = |what: &str| format!("field `{}` must be {}", k, what);
+            let num = || v.as_num().ok_or_else(|| bad("a number"));
+            let count = || match v.as_num() {
+                Some(x) if x >= 0.0 && x.fract() == 0.0 => Ok(x as usize),
+                _ => Err(bad("a non-negative integer")),
+            };
+            let opt_num = || match v {
+                Json::Null => Ok(None),
+                _ => v.as_num().map(Some).ok_or_else(|| bad("a number or null")),
+            };
+            let weight = || match v.as_num() {
+                Some(x) if x.is_finite() && x >= 0.0 => Ok(x),
+                _ => Err(bad("a finite non-negative number")),
+            };
+            let name = || v.as_str().ok_or_else(|| bad("a string"));
+            match k.as_str() {
+                "ratio" => match num()? {
+                    r if (0.0..=1.0).contains(&r) => self.ratio = r,
+                    _ => return Err(bad("in [0, 1]")),
+                },
+                "max_error" => self.max_error = opt_num()?,
+                "feature_angle" => self.feature_angle = opt_num()?,
+                "feature_weight" => self.feature_weight = weight()?,
+                "preserve_boundary" => {
+                    self.preserve_boundary = v.as_bool().ok_or_else(|| bad("true or false"))?
+                },
+                "strategy" => self.strategy = name()?.parse().map_err(|e: String| bad(&e))?,
+                "placement" => self.placement = name()?.parse().map_err(|e: String| bad(&e))?,
+                "threads" => self.threads = count()?.max(1),
+                "progress_interval" => self.progress_interval = count()?,
+                "skin_penalty" => self.skin_penalty = weight()?,
+                "color_weight" => self.color_weight = weight()?,
+                "quadric_weight" => {
+                    self.quadric_weight = name()?.parse().map_err(|e: String| bad(&e))?
+                },
+                "normalize" => self.normalize = v.as_bool().ok_or_else(|| bad("true or false"))?,
+                _ => {
+                    return Err(format!(
+                        "unknown field `{}`, expected one of {}",
+                        k,
+                        FIELDS.join(", ")
+                    ))
+                },
+            }
+        }
+        Ok(())
+    }
+
+    pub fn from_json(j: &Json) -> Result<Self, String> {
+        let mut ret = Self::default();
+        ret.apply_json(j)?;
+        Ok(ret)
+    }
+
+    // 预设文件是以预设名为键的 JSON 对象；name 为 None 时整个文件就是一组参数
+    pub fn load_preset(path: &str, name: Option<&str>) -> Result<Self, String> {
+        let s = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        let j = Json::parse(&s).map_err(|e| format!("{}: {}", path, e))?;
+        let j = match name {
+            Some(name) => j.get(name).ok_or_else(|| {
+                let names: Vec<_> = match &j {
+                    Json::Obj(o) => o.iter().map(|(k, _)| k.as_str()).collect(),
+                    _ => vec![],
+                };
+                format!("{}: no preset named `{}` (found {})", path, name, names.join(", "))
+            })?,
+            None => &j,
+        };
+        Self::from_json(j).map_err(|e| match name {
+            Some(name) => format!("{}: preset `{}`: {}", path, name, e),
+            None => format!("{}: {}", path, e),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PlacementPolicy, Strategy};
+
+    #[test]
+    fn round_trip() {
+        let opts = SimplifyOptions {
+            ratio: 0.25,
+            max_error: Some(1e-4),
+            strategy: Strategy::Volume,
+            placement: PlacementPolicy::Best,
+            threads: 4,
+            quadric_weight: crate::NormalWeight::Angle,
+            normalize: true,
+            ..Default::default()
+        };
+        let back = SimplifyOptions::from_json(&Json::parse(&opts.to_json().pretty()).unwrap());
+        assert_eq!(back.unwrap().to_json(), opts.to_json());
+    }
+
+    #[test]
+    fn presets() {
+        let path = std::env::temp_dir().join("mesh_simplification_presets.json");
+        let path = path.to_str().unwrap();
+        fs::write(
+            path,
+            r#"{
+                "props": {"ratio": 0.1, "placement": "endpoint"},
+                "terrain": {"ratio": 0.2, "preserve_boundary": true, "feature_angel": 30},
+                "characters": {"ratio": 2, "threads": 1.5}
+            }"#,
+        )
+        .unwrap();
+        let props = SimplifyOptions::load_preset(path, Some("props")).unwrap();
+        assert_eq!((props.ratio, props.placement), (0.1, PlacementPolicy::Endpoint));
+        assert_eq!(props.feature_weight, SimplifyOptions::default().feature_weight);
+        let err = SimplifyOptions::load_preset(path, Some("terrain")).unwrap_err();
+        assert!(err.contains("preset `terrain`: unknown field `feature_angel`"));
+        let err = SimplifyOptions::load_preset(path, Some("characters")).unwrap_err();
+        assert!(err.ends_with("field `ratio` must be in [0, 1]"));
+        let err = SimplifyOptions::load_preset(path, Some("rocks")).unwrap_err();
+        assert!(err.ends_with("no preset named `rocks` (found props, terrain, characters)"));
+        assert!(SimplifyOptions::load_preset(path, None).is_err());
+    }
+
+    #[test]
+    fn weights() {
+        let parse = |s: &str| SimplifyOptions::from_json(&Json::parse(s).unwrap());
+        assert_eq!(parse(r#"{"skin_penalty": 1000}"#).unwrap().skin_penalty, 1000.0);
+        for s in [r#"{"feature_weight": -1}"#, r#"{"color_weight": 1e999}"#] {
+            assert!(parse(s).unwrap_err().ends_with("must be a finite non-negative number"));
+        }
+        assert!(parse(r#"{"crease_edges": [[0, 1]]}"#).unwrap_err().contains("unknown field"));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::Flt;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    // 当前面数与目标面数
+    pub faces: usize,
+    pub target: usize,
+    // 目前为止执行过的收缩的最大代价
+    pub error: Flt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+    Continue,
+    // 停止简化，返回当前已简化的网格
+    Cancel,
+}
+
+// 简化过程中每删除一定数量的面被调用一次，分块并行简化时会从多个线程调用
+pub trait Observer: Send + Sync {
+    fn progress(&self, p: &Progress) -> Control;
+}
+
+impl<F: Fn(&Progress) -> Control + Send + Sync> Observer for F {
+    fn progress(&self, p: &Progress) -> Control {
+        self(p)
+    }
+}
+
+impl fmt::Debug for dyn Observer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Observer")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mesh, SimplifyOptions};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn cancel() {
+        let mesh = Mesh::new("assets/sphere.obj");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c = calls.clone();
+        let opts = SimplifyOptions {
+            ratio: 0.1,
+            progress_interval: 100,
+            observer: Some(Arc::new(move |p: &Progress| {
+                assert!(p.faces >= p.target);
+                match c.fetch_add(1, Ordering::SeqCst) {
+                    5 => Control::Cancel,
+                    _ => Control::Continue,
+                }
+            })),
+            ..Default::default()
+        };
+        let out = mesh.simplify_with(&opts);
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+        assert!(out.tri.len() < mesh.tri.len() && out.tri.len() > mesh.tri.len() / 2);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::normal::angle;
+use crate::{Flt, Mat, NormalWeight, Vct};
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+
+// 解最优点时 3x3 部分的条件数超过此值则认为病态
+const COND_MAX: Flt = 1e5;
+
+// 对称的 4x4 误差矩阵，只保存上三角的 10 个系数：
+// [0 1 2 3]
+// [  4 5 6]
+// [    7 8]
+// [      9]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Quadric {
+    pub data: [Flt; 10],
+}
+
+impl Quadric {
+    // 到平面 n·x + d = 0 的距离的平方（n 为单位向量时），即 [n d]^T [n d]
+    pub fn from_plane(n: Vct, d: Flt) -> Self {
+        let (a, b, c) = (n.x, n.y, n.z);
+        Self { data: [a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d] }
+    }
+
+    // 过 p、法向为 norm 的平面
+    pub fn plane(norm: Vct, p: Vct) -> Self {
+        Self::from_plane(norm, -norm.dot(p))
+    }
+
+    // 三角形所在平面，退化的三角形为零
+    pub fn triangle(a: Vct, b: Vct, c: Vct) -> Self {
+        let n = (a - c) % (b - c);
+        match n.len2() > 0.0 {
+            true => Self::plane(n.norm(), c),
+            false => Self::default(),
+        }
+    }
+
+    pub fn to_mat(&self) -> Mat {
+        let q = &self.data;
+        Mat {
+            data: [
+                [q[0], q[1], q[2], q[3]],
+                [q[1], q[4], q[5], q[6]],
+                [q[2], q[5], q[7], q[8]],
+                [q[3], q[6], q[8], q[9]],
+            ],
+        }
+    }
+
+    // v^T Q v，v 的第四维为 1
+    pub fn evaluate(&self, v: Vct) -> Flt {
+        let q = &self.data;
+        let (x, y, z) = (v.x, v.y, v.z);
+        x * (q[0] * x + 2.0 * (q[1] * y + q[2] * z + q[3]))
+            + y * (q[4] * y + 2.0 * (q[5] * z + q[6]))
+            + z * (q[7] * z + 2.0 * q[8])
+            + q[9]
+    }
+
+    // 左上角 3x3 部分乘以 v
+    pub fn mul_vct3(&self, v: Vct) -> Vct {
+        let q = &self.data;
+        Vct::new(
+            q[0] * v.x + q[1] * v.y + q[2] * v.z,
+            q[1] * v.x + q[4] * v.y + q[5] * v.z,
+            q[2] * v.x + q[5] * v.y + q[7] * v.z,
+        )
+    }
+
+    // 一次项 (q03, q13, q23)
+    pub fn linear(&self) -> Vct {
+        Vct::new(self.data[3], self.data[6], self.data[8])
+    }
+
+    // 令梯度为零的点，3x3 部分病态时为 None
+    pub fn optimal(&self) -> Option<Vct> {
+        let (x, cond) = self.solve(Vct::zero());
+        Some(x).filter(|_| cond <= COND_MAX)
+    }
+
+    // 病态时在退化方向上取离 x0 最近的点
+    pub fn optimal_near(&self, x0: Vct) -> Vct {
+        self.solve(x0).0
+    }
+
+    // 条件数不超过 COND_MAX 时即为精确解，否则截断小特征值的方向；同时返回条件数
+    fn solve(&self, x0: Vct) -> (Vct, Flt) {
+        self.to_mat().solve3(-self.linear(), x0, 1.0 / COND_MAX)
+    }
+}
+
+impl Add for Quadric {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        let mut ret = self;
+        ret += rhs;
+        ret
+    }
+}
+
+impl AddAssign for Quadric {
+    fn add_assign(&mut self, rhs: Self) {
+        self.data.iter_mut().zip(rhs.data.iter()).for_each(|(a, b)| *a += b);
+    }
+}
+
+impl Sub for Quadric {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        let mut ret = self;
+        ret -= rhs;
+        ret
+    }
+}
+
+impl SubAssign for Quadric {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.data.iter_mut().zip(rhs.data.iter()).for_each(|(a, b)| *a -= b);
+    }
+}
+
+impl Mul<Flt> for Quadric {
+    type Output = Self;
+    fn mul(self, rhs: Flt) -> Self {
+        let mut ret = self;
+        ret.data.iter_mut().for_each(|x| *x *= rhs);
+        ret
+    }
+}
+
+// 面的误差矩阵在三个角上的权重：均匀为 1，面积加权为面积，角度加权为该角的弧度
+pub fn corner_weights(p: [Vct; 3], weight: NormalWeight) -> [Flt; 3] {
+    match weight {
+        NormalWeight::Uniform => [1.0; 3],
+        NormalWeight::Area => [((p[1] - p[0]) % (p[2] - p[0])).len() * 0.5; 3],
+        NormalWeight::Angle => {
+            [0, 1, 2].map(|k| angle(p[(k + 1) % 3] - p[k], p[(k + 2) % 3] - p[k]))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EPS;
+
+    #[test]
+    fn evaluate() {
+        let (a, b, c) =
+            (Vct::new(0.3, 1.0, -2.0), Vct::new(1.5, 0.2, 0.0), Vct::new(0.0, 2.0, 1.0));
+        let q = Quadric::triangle(a, b, c) * 2.0 + Quadric::plane(Vct::new(0.0, 0.0, 1.0), a);
+        let v = Vct::new(0.7, -1.1, 2.5);
+        assert!((q.evaluate(v) - q.to_mat().multiply_by_vct(v)).abs() < EPS);
+        assert!((q.mul_vct3(v) - q.to_mat().mul_vct3(v)).len() < EPS);
+        assert!(Quadric::triangle(a, b, c).evaluate((a + b + c) / 3.0).abs() < EPS);
+        assert_eq!(Quadric::triangle(a, b, b), Quadric::default());
+    }
+
+    #[test]
+    fn optimal() {
+        // 三个坐标平面交于 p
+        let p = Vct::new(1.0, -2.0, 3.0);
+        let q = Quadric::plane(Vct::new(1.0, 0.0, 0.0), p)
+            + Quadric::plane(Vct::new(0.0, 1.0, 0.0), p)
+            + Quadric::plane(Vct::new(0.0, 0.0, 1.0), p);
+        assert!((q.optimal().unwrap() - p).len() < EPS);
+        // 只有一个平面时病态，退化方向上取离 x0 最近的点
+        let q = Quadric::plane(Vct::new(0.0, 0.0, 1.0), p);
+        assert!(q.optimal().is_none());
+        assert!((q.optimal_near(Vct::zero()) - Vct::new(0.0, 0.0, 3.0)).len() < EPS);
+        // 两个平面几乎平行时条件数过大，也按病态处理
+        let q = q
+            + Quadric::plane(Vct::new(0.0, 1e-4, 1.0).norm(), p)
+            + Quadric::plane(Vct::new(1e-4, 0.0, 1.0).norm(), p);
+        assert!(q.optimal().is_none());
+        assert!((q.optimal_near(Vct::zero()) - Vct::new(0.0, 0.0, 3.0)).len() < 1e-3);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{Flt, Mesh};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RepairReport {
+    pub merged_vertices: usize,
+    pub degenerate_faces: usize,
+    pub duplicate_faces: usize,
+    pub unreferenced_vertices: usize,
+}
+
+impl Mesh {
+    // 合并距离不超过 weld 的顶点，删除退化的面、重复的面（不计朝向）以及不被任何面引用的顶点，
+    // weld 为 0 时只合并位置完全相同的顶点
+    pub fn repair(&self, weld: Flt) -> (Self, RepairReport) {
+        let mut report = RepairReport::default();
+        let mut rep: Vec<_> = (0..self.pos.len()).collect();
+        if weld > 0.0 {
+            let key = |x: Flt| (x / weld).floor() as i64;
+            let mut grid: HashMap<_, Vec<usize>> = HashMap::new();
+            for (v, p) in self.pos.iter().enumerate() {
+                let (x, y, z) = (key(p.x), key(p.y), key(p.z));
+                let near = (0..27).find_map(|k| {
+                    let c = (x + k % 3 - 1, y + k / 3 % 3 - 1, z + k / 9 - 1);
+                    grid.get(&c)?.iter().find(|&&u| (self.pos[u] - *p).len() <= weld).cloned()
+                });
+                match near {
+                    Some(u) => rep[v] = u,
+                    None => grid.entry((x, y, z)).or_default().push(v),
+                }
+            }
+        } else {
+            let mut seen = HashMap::new();
+            for (v, p) in self.pos.iter().enumerate() {
+                rep[v] = *seen.entry((p.x.to_bits(), p.y.to_bits(), p.z.to_bits())).or_insert(v);
+            }
+        }
+        report.merged_vertices = (0..self.pos.len()).filter(|&v| rep[v] != v).count();
+
+        let (mut tri, mut nrm_tri) = (vec![], vec![]);
+        let mut faces = HashSet::new();
+        for (i, &(a, b, c)) in self.tri.iter().enumerate() {
+            let (a, b, c) = (rep[a], rep[b], rep[c]);
+            let n = (self.pos[b] - self.pos[a]) % (self.pos[c] - self.pos[a]);
+            if a == b || b == c || c == a || n.len2() <= 0.0 {
+                report.degenerate_faces += 1;
+                continue;
+            }
+            let mut k = [a, b, c];
+            k.sort_unstable();
+            if !faces.insert(k) {
+                report.duplicate_faces += 1;
+                continue;
+            }
+            tri.push((a, b, c));
+            if self.has_normals() {
+                nrm_tri.push(self.nrm_tri[i]);
+            }
+        }
+
+        let mut id = vec![usize::MAX; self.pos.len()];
+        let mut src = vec![];
+        for t in tri.iter_mut() {
+            for x in [&mut t.0, &mut t.1, &mut t.2] {
+                if id[*x] == usize::MAX {
+                    id[*x] = src.len();
+                    src.push(*x);
+                }
+                *x = id[*x];
+            }
+        }
+        report.unreferenced_vertices = self.pos.len() - report.merged_vertices - src.len();
+        let mut ret = Self::from_raw(src.iter().map(|&v| self.pos[v]).collect(), tri);
+        if self.has_normals() {
+            ret.nrm = self.nrm.clone();
+            ret.nrm_tri = nrm_tri;
+        }
+        if self.has_skin() {
+            ret.skin = src.iter().map(|&v| self.skin[v]).collect();
+        }
+        if self.has_colors() {
+            ret.color = src.iter().map(|&v| self.color[v]).collect();
+        }
+        (ret, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vct;
+
+    #[test]
+    fn repair() {
+        let mesh = Mesh::new("assets/cube.obj");
+        let mut broken = mesh.clone();
+        // 每个面都用自己的一份顶点，再加上一个退化面、一个反向的重复面和一个孤立顶点
+        broken.pos =
+            mesh.tri.iter().flat_map(|&(a, b, c)| [a, b, c]).map(|v| mesh.pos[v]).collect();
+        broken.tri = (0..mesh.tri.len()).map(|i| (i * 3, i * 3 + 1, i * 3 + 2)).collect();
+        broken.tri.push((0, 0, 1));
+        broken.tri.push((2, 1, 0));
+        broken.pos[0] += Vct::one() * 1e-4;
+        broken.pos.push(Vct::one() * 10.0);
+        let (out, report) = broken.repair(1e-3);
+        assert_eq!(out.tri.len(), mesh.tri.len());
+        assert_eq!(out.pos.len(), mesh.pos.len());
+        assert_eq!(report.merged_vertices, mesh.tri.len() * 3 - mesh.pos.len());
+        assert_eq!((report.degenerate_faces, report.duplicate_faces), (1, 1));
+        assert_eq!(report.unreferenced_vertices, 1);
+        let s = out.stats();
+        assert_eq!((s.boundary_edges, s.components), (0, 1));
+        assert_eq!(broken.repair(0.0).1.merged_vertices, report.merged_vertices - 1);
+    }
+}
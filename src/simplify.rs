@@ -0,0 +1,1054 @@
+use crate::color::ColorQuadric;
+use crate::cost::{CollapseContext, CollapseCost, Placement, QuadricCost, QuadricPlacement};
+use crate::float::Float;
+use crate::normal::{find, NORMAL_CREASE};
+use crate::parallel::{par_chunks_mut, par_map, PATCH_FACES};
+use crate::progress::{Control, Observer, Progress};
+use crate::quadric::{corner_weights, Quadric};
+use crate::{Aabb, Flt, HalfEdgeMesh, Mesh, NormalWeight, Vct, VertexSource, PI};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+use std::sync::Arc;
+
+const COST_EPS: Flt = 1e50;
+const DIST_EPS: Flt = 1e50;
+
+// 堆中只记录端点与入堆时端点的版本号，新位置在出堆时重新计算
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct State {
+    pub cost: Flt,
+    pub v1: u32,
+    pub v2: u32,
+    pub ver1: u32,
+    pub ver2: u32,
+}
+
+impl State {
+    pub fn new(cost: Flt, v1: usize, v2: usize, ver1: u32, ver2: u32) -> Self {
+        let id = |v: usize| u32::try_from(v).expect("too many vertices");
+        Self { cost, v1: id(v1), v2: id(v2), ver1, ver2 }
+    }
+}
+
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &State) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &State) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Feature {
+    Smooth,
+    Curve,
+    Corner,
+}
+
+impl Feature {
+    fn new(degree: usize) -> Self {
+        match degree {
+            0 => Feature::Smooth,
+            2 => Feature::Curve,
+            _ => Feature::Corner,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    // 新顶点取误差矩阵的最优点
+    Quadric,
+    // Lindstrom–Turk 式：在保持体积与边界面积的约束下最小化误差
+    Volume,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlacementPolicy {
+    Optimal,
+    // 半边收缩：新顶点取两个端点中代价较小的一个
+    Endpoint,
+    // 在两个端点、中点与最优点中取代价最小的
+    Best,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", if *self == Strategy::Quadric { "quadric" } else { "volume" })
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "quadric" => Ok(Strategy::Quadric),
+            "volume" => Ok(Strategy::Volume),
+            _ => Err("quadric or volume".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for PlacementPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            PlacementPolicy::Optimal => "optimal",
+            PlacementPolicy::Endpoint => "endpoint",
+            PlacementPolicy::Best => "best",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for PlacementPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "optimal" => Ok(PlacementPolicy::Optimal),
+            "endpoint" => Ok(PlacementPolicy::Endpoint),
+            "best" => Ok(PlacementPolicy::Best),
+            _ => Err("optimal, endpoint or best".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SimplifyOptions {
+    pub ratio: Flt,
+    // 下一次收缩的代价超过该值时提前停止，即使还没有达到 ratio
+    pub max_error: Option<Flt>,
+    // 二面角（角度制）超过该值的边视为特征边
+    pub feature_angle: Option<Flt>,
+    // 用户给出的折痕边，下标与 Mesh::pos 一致从 0 开始（OBJ 读入时按 (v, vt, vn) 拆开之后的下标），
+    // 不是网格中的边的被忽略；文件中的下标用 add_crease_edges 转换
+    pub crease_edges: Vec<(usize, usize)>,
+    pub feature_weight: Flt,
+    pub preserve_boundary: bool,
+    // 逐顶点的权重与锁定标记，长度不足的部分分别视为 1 和未锁定
+    pub weights: Vec<Flt>,
+    pub locked: Vec<bool>,
+    pub strategy: Strategy,
+    pub placement: PlacementPolicy,
+    // 大于 1 时并行初始化，面数足够多时分块并行收缩
+    pub threads: usize,
+    // 每删除 progress_interval 个面以及结束时通知 observer
+    pub observer: Option<Arc<dyn Observer>>,
+    pub progress_interval: usize,
+    // 两端点主骨骼不同时在收缩代价上额外增加的值，为 0 时不考虑骨骼
+    pub skin_penalty: Flt,
+    // 颜色分量相差 1 时的误差相当于偏离包围盒对角线的多少倍
+    pub color_weight: Flt,
+    // 面的误差矩阵累加到顶点时的权重：均匀、按面积或按该角的大小
+    pub quadric_weight: NormalWeight,
+    // 先把输入缩放到最长边为 1 的包围盒中，收缩代价与 max_error 都以此为单位
+    pub normalize: bool,
+}
+
+impl Default for SimplifyOptions {
+    fn default() -> Self {
+        Self {
+            ratio: 0.5,
+            max_error: None,
+            feature_angle: None,
+            crease_edges: vec![],
+            feature_weight: 100.0,
+            preserve_boundary: false,
+            weights: vec![],
+            locked: vec![],
+            strategy: Strategy::Quadric,
+            placement: PlacementPolicy::Optimal,
+            threads: 1,
+            observer: None,
+            progress_interval: 1000,
+            skin_penalty: 0.0,
+            color_weight: 0.01,
+            quadric_weight: NormalWeight::Uniform,
+            normalize: false,
+        }
+    }
+}
+
+// 一次边收缩：v2 并入 v1，v1 移动到 pos，下标均为输入网格中的下标
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Collapse {
+    pub v1: usize,
+    pub v2: usize,
+    pub pos: Vct,
+    pub cost: Flt,
+}
+
+// 分块并行简化时各块带到最后一遍整体简化的状态：每个顶点的权重（为空时取 opts.weights）与收缩的最大代价
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Carry {
+    pub weights: Vec<Flt>,
+    pub error: Flt,
+}
+
+#[derive(Clone, Debug)]
+pub struct Simplified<T = Flt> {
+    pub mesh: Mesh<T>,
+    // 每个输入顶点最终并入的输出顶点，不在任何输出面上时为 usize::MAX
+    pub vertex_map: Vec<usize>,
+    // 按执行顺序排列的收缩记录
+    pub collapses: Vec<Collapse>,
+}
+
+impl SimplifyOptions {
+    // 每行为 "下标 权重" 或 "下标 lock"，下标与文件中的 v 一致从 1 开始，# 之后为注释；
+    // 读入时被拆开的顶点都取该下标的值；出错时返回带行号的信息，不修改参数
+    pub fn load_vertex_marks(&mut self, path: &str, source: &VertexSource) -> Result<(), String> {
+        let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path, e))?;
+        let mut marks = vec![];
+        for (k, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("cannot read {}: {}", path, e))?;
+            let err = |msg: String| Err(format!("{}:{}: {}", path, k + 1, msg));
+            let mut w = line.split('#').next().unwrap().split_whitespace();
+            let i = match w.next() {
+                Some(i) => i,
+                None => continue,
+            };
+            let i = match i.parse::<usize>() {
+                Ok(i) if (1..=source.count).contains(&i) => i - 1,
+                _ => {
+                    return err(format!("vertex index must be in 1..={}, got {}", source.count, i))
+                },
+            };
+            match w.next() {
+                Some("lock") => marks.push((i, None)),
+                Some(x) => match x.parse::<Flt>() {
+                    Ok(x) if x.is_finite() && x >= 0.0 => marks.push((i, Some(x))),
+                    _ => return err(format!("invalid weight of vertex {}: {}", i + 1, x)),
+                },
+                None => return err(format!("missing weight of vertex {}", i + 1)),
+            }
+        }
+        let n = source.src.len();
+        self.weights.resize(n, 1.0);
+        self.locked.resize(n, false);
+        let mut at: HashMap<usize, Vec<usize>> = marks.iter().map(|&(i, _)| (i, vec![])).collect();
+        for (v, s) in source.src.iter().enumerate() {
+            if let Some(a) = at.get_mut(s) {
+                a.push(v);
+            }
+        }
+        for (i, mark) in marks {
+            for &v in &at[&i] {
+                match mark {
+                    Some(w) => self.weights[v] = w,
+                    None => self.locked[v] = true,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 每行为两个顶点下标 "a b"，下标与文件中的 v 一致从 1 开始，# 之后为注释；出错时返回带行号的信息，不修改参数
+    pub fn load_crease_edges(&mut self, path: &str, source: &VertexSource) -> Result<(), String> {
+        let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path, e))?;
+        let mut pairs = vec![];
+        for (k, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("cannot read {}: {}", path, e))?;
+            let w: Vec<_> = line.split('#').next().unwrap().split_whitespace().collect();
+            let index =
+                |x: &str| x.parse::<usize>().ok().filter(|i| (1..=source.count).contains(i));
+            let pair = match w.as_slice() {
+                [] => continue,
+                [a, b] => index(a).zip(index(b)),
+                _ => None,
+            };
+            match pair {
+                Some(p) => pairs.push(p),
+                None => {
+                    return Err(format!(
+                        "{}:{}: expected two vertex indices in 1..={}, got `{}`",
+                        path,
+                        k + 1,
+                        source.count,
+                        line.trim()
+                    ))
+                },
+            }
+        }
+        self.add_crease_edges(&pairs, source)
+    }
+
+    // pairs 中的下标与文件中的 v 一致从 1 开始，读入时被拆开的顶点的每一份之间都加上折痕边
+    pub fn add_crease_edges(
+        &mut self,
+        pairs: &[(usize, usize)],
+        source: &VertexSource,
+    ) -> Result<(), String> {
+        let bad = |i: &usize| !(1..=source.count).contains(i);
+        if let Some(&(a, b)) = pairs.iter().find(|&&(a, b)| bad(&a) || bad(&b)) {
+            return Err(format!(
+                "crease edge ({}, {}): vertex index must be in 1..={}",
+                a, b, source.count
+            ));
+        }
+        let mut at: HashMap<usize, Vec<usize>> = HashMap::new();
+        source.src.iter().enumerate().for_each(|(v, &s)| at.entry(s + 1).or_default().push(v));
+        for &(a, b) in pairs {
+            for &u in at.get(&a).into_iter().flatten() {
+                self.crease_edges.extend(at.get(&b).into_iter().flatten().map(|&v| (u, v)));
+            }
+        }
+        Ok(())
+    }
+
+    // 检查参数是否适用于有 vertices 个顶点的网格；简化时参数不合法会 panic
+    pub fn validate(&self, vertices: usize) -> Result<(), String> {
+        match self.crease_edges.iter().find(|&&(a, b)| a.max(b) >= vertices) {
+            Some(&(a, b)) => Err(format!(
+                "crease edge ({}, {}) is out of range, the mesh has {} vertices",
+                a, b, vertices
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+// v 在边 p1p2 上投影的参数，用来按新顶点位置混合端点的属性
+pub(crate) fn edge_param(p1: Vct, p2: Vct, v: Vct) -> Flt {
+    let e = p2 - p1;
+    match e.len2() > 0.0 {
+        true => ((v - p1).dot(e) / e.len2()).clamp(0.0, 1.0),
+        false => 0.0,
+    }
+}
+
+impl Mesh {
+    // 二面角超过 angle（角度制）的边，非流形边，以及可选的边界边
+    pub fn feature_edges(&self, angle: Option<Flt>, boundary: bool) -> Vec<(usize, usize)> {
+        let fnrm = self.face_normals();
+        let mut ret: Vec<_> = self
+            .edge_faces()
+            .into_iter()
+            .filter(|(_, f)| match (f.len(), angle) {
+                (1, _) => boundary,
+                (2, Some(angle)) => {
+                    fnrm[f[0]].dot(fnrm[f[1]]).clamp(-1.0, 1.0).acos() > angle * PI / 180.0
+                },
+                (2, None) => false,
+                _ => angle.is_some(),
+            })
+            .map(|(e, _)| e)
+            .collect();
+        ret.sort_unstable();
+        ret
+    }
+}
+
+impl<T: Float> Mesh<T> {
+    pub fn simplify(&self, ratio: Flt) -> Self {
+        self.simplify_with(&SimplifyOptions { ratio, ..Default::default() })
+    }
+
+    pub fn simplify_with(&self, opts: &SimplifyOptions) -> Self {
+        self.simplify_detailed(opts).mesh
+    }
+
+    // 同时返回输入顶点到输出顶点的对应关系与收缩记录
+    pub fn simplify_detailed(&self, opts: &SimplifyOptions) -> Simplified<T> {
+        let placement = QuadricPlacement { strategy: opts.strategy, policy: opts.placement };
+        self.simplify_custom_detailed(opts, &QuadricCost, &placement)
+    }
+
+    // 用自定义的收缩代价与新顶点位置简化，opts 中的 strategy 与 placement 不再起作用
+    pub fn simplify_custom(
+        &self,
+        opts: &SimplifyOptions,
+        cost: &dyn CollapseCost,
+        placement: &dyn Placement,
+    ) -> Self {
+        self.simplify_custom_detailed(opts, cost, placement).mesh
+    }
+
+    pub fn simplify_custom_detailed(
+        &self,
+        opts: &SimplifyOptions,
+        cost: &dyn CollapseCost,
+        placement: &dyn Placement,
+    ) -> Simplified<T> {
+        if let Err(e) = opts.validate(self.pos.len()) {
+            panic!("{}", e);
+        }
+        // 误差矩阵总是在 f64 下累积，f32 的输入只在返回时转换回去
+        let mut input = self.cast::<Flt>();
+        let (mut lo, mut scale) = (Vct::zero(), 1.0);
+        if opts.normalize && !input.pos.is_empty() {
+            let b = input.bbox();
+            lo = b.lo;
+            scale = b.longest_side();
+            if scale <= 0.0 {
+                scale = 1.0;
+            }
+            input.pos.iter_mut().for_each(|p| *p = (*p - lo) / scale);
+        }
+        let (mut mesh, src, mut collapses) =
+            if opts.threads > 1 && self.tri.len() >= opts.threads * PATCH_FACES {
+                input.simplify_parallel(opts, cost, placement)
+            } else {
+                let (mesh, src, collapses, _) =
+                    input.simplify_serial(opts, cost, placement, &Carry::default());
+                (mesh, src, collapses)
+            };
+        // 沿收缩记录找到每个输入顶点最终并入的顶点
+        let mut f: Vec<_> = (0..self.pos.len()).collect();
+        collapses.iter().for_each(|c| f[c.v2] = c.v1);
+        let mut id = vec![usize::MAX; self.pos.len()];
+        src.iter().enumerate().for_each(|(j, &v)| id[v] = j);
+        let vertex_map = (0..self.pos.len()).map(|v| id[find(&mut f, v)]).collect();
+        if opts.normalize {
+            mesh.pos.iter_mut().for_each(|p| *p = *p * scale + lo);
+            collapses.iter_mut().for_each(|c| c.pos = c.pos * scale + lo);
+        }
+        // 收缩后原来的法向不再对应，输入带法向时重新计算
+        let mut mesh: Mesh<T> = mesh.cast();
+        if self.has_normals() {
+            mesh.compute_normals(NormalWeight::Angle, Some(NORMAL_CREASE));
+        }
+        Simplified { mesh, vertex_map, collapses }
+    }
+}
+
+impl Mesh {
+    // 同时返回每个输出顶点在输入中的下标、收缩记录与输出顶点的状态
+    pub(crate) fn simplify_serial(
+        &self,
+        opts: &SimplifyOptions,
+        cost: &dyn CollapseCost,
+        placement: &dyn Placement,
+        carry: &Carry,
+    ) -> (Self, Vec<usize>, Vec<Collapse>, Carry) {
+        let (mut pos, mut tri) = (self.pos.clone(), self.tri.clone());
+        let mut skin = if self.has_skin() { self.skin.clone() } else { vec![] };
+        let mut color = if self.has_colors() { self.color.clone() } else { vec![] };
+        let n = pos.len();
+        let threads = opts.threads.max(1);
+        let mut q = vec![Quadric::default(); n];
+        // 每个顶点的误差矩阵按权重缩放，锁定的顶点不参与任何收缩
+        let mut w: Vec<_> = match carry.weights.is_empty() {
+            true => (0..n).map(|i| *opts.weights.get(i).unwrap_or(&1.0)).collect(),
+            false => carry.weights.clone(),
+        };
+        let lock: Vec<_> = (0..n).map(|i| *opts.locked.get(i).unwrap_or(&false)).collect();
+        let mut head = vec![vec![0; 0]; n];
+
+        tri.iter().enumerate().for_each(|(i, &(v1, v2, v3))| {
+            head[v1].push(i);
+            head[v2].push(i);
+            head[v3].push(i);
+        });
+        // 面 i 在三个角上的权重
+        macro_rules! face_w {
+            ($i:expr) => {{
+                let (a, b, c) = tri[$i];
+                corner_weights([pos[a], pos[b], pos[c]], opts.quadric_weight)
+            }};
+        }
+        // v 是面 i 的第几个角
+        macro_rules! slot {
+            ($i:expr, $v:expr) => {
+                if tri[$i].0 == $v {
+                    0
+                } else if tri[$i].1 == $v {
+                    1
+                } else {
+                    2
+                }
+            };
+        }
+        par_chunks_mut(&mut q, threads, |start, chunk| {
+            chunk.iter_mut().enumerate().for_each(|(k, qv)| {
+                let v = start + k;
+                head[v].iter().for_each(|&i| {
+                    let (a, b, c) = tri[i];
+                    *qv += Quadric::triangle(pos[a], pos[b], pos[c])
+                        * (face_w!(i)[slot!(i, v)] * w[v]);
+                });
+            });
+        });
+
+        // 面 i 的颜色误差矩阵，没有颜色时 cq 为空；颜色误差乘以 (color_weight * 包围盒对角线)^2，
+        // 与几何误差的量纲一致
+        macro_rules! face_cq {
+            ($i:expr) => {{
+                let (a, b, c) = tri[$i];
+                ColorQuadric::new([pos[a], pos[b], pos[c]], [color[a], color[b], color[c]])
+            }};
+        }
+        let mut cq = vec![];
+        let mut cw = 0.0;
+        if !color.is_empty() {
+            cq = vec![ColorQuadric::default(); n];
+            par_chunks_mut(&mut cq, threads, |start, chunk| {
+                chunk.iter_mut().enumerate().for_each(|(k, cv)| {
+                    let v = start + k;
+                    head[v]
+                        .iter()
+                        .for_each(|&i| *cv += face_cq!(i) * (face_w!(i)[slot!(i, v)] * w[v]));
+                });
+            });
+            cw = (opts.color_weight * Aabb::from_points(pos.iter().cloned()).diagonal()).powi(2);
+        }
+
+        // 特征边上的顶点只能沿特征线滑动，角点不能移动；
+        // qc 是沿特征边、垂直于相邻面的约束平面的误差矩阵，没有特征边时为空
+        let mut fadj = vec![vec![0; 0]; n];
+        let mut qc = vec![];
+        let mut feature = self.feature_edges(opts.feature_angle, opts.preserve_boundary);
+        let edge_faces = self.edge_faces();
+        feature.extend(
+            opts.crease_edges
+                .iter()
+                .map(|&(a, b)| (a.min(b), a.max(b)))
+                .filter(|e| edge_faces.contains_key(e)),
+        );
+        feature.sort_unstable();
+        feature.dedup();
+        if !feature.is_empty() {
+            qc = vec![Quadric::default(); n];
+            let fnrm = self.face_normals();
+            feature.iter().filter(|&&(a, b)| a != b).for_each(|&(a, b)| {
+                fadj[a].push(b);
+                fadj[b].push(a);
+                edge_faces.get(&(a, b)).iter().flat_map(|f| f.iter()).for_each(|&i| {
+                    let norm = (pos[b] - pos[a]) % fnrm[i];
+                    // 面积加权时约束平面也乘以相邻面的面积，与几何误差的量纲一致
+                    let area = match opts.quadric_weight {
+                        NormalWeight::Area => face_w!(i)[0],
+                        _ => 1.0,
+                    };
+                    if norm.len2() > 0.0 {
+                        let qk = Quadric::plane(norm.norm(), pos[a]) * (opts.feature_weight * area);
+                        qc[a] += qk * w[a];
+                        qc[b] += qk * w[b];
+                    }
+                });
+            });
+        }
+
+        // 收缩 (v1, v2) 时保留 v1、删除 v2，并增加所有误差矩阵改变了的顶点的版本号，
+        // 堆中记录的版本号与当前不同的边即为过期的边
+        let mut dele = vec![false; n];
+        let mut ver = vec![0; n];
+        let mut heap = BinaryHeap::new();
+
+        macro_rules! allowed {
+            ($v1:expr, $v2:expr) => {
+                !lock[$v1]
+                    && !lock[$v2]
+                    && match (Feature::new(fadj[$v1].len()), Feature::new(fadj[$v2].len())) {
+                        (Feature::Smooth, _) | (_, Feature::Smooth) => true,
+                        (Feature::Corner, Feature::Corner) => false,
+                        _ => fadj[$v1].contains(&$v2),
+                    }
+            };
+        }
+
+        macro_rules! in_tri {
+            ($i:expr, $v:expr) => {
+                tri[$i].0 == $v || tri[$i].1 == $v || tri[$i].2 == $v
+            };
+        }
+
+        macro_rules! is_valid_tri {
+            ($i:expr) => {
+                !(dele[tri[$i].0] || dele[tri[$i].1] || dele[tri[$i].2])
+            };
+        }
+
+        // 流形网格同时维护半边结构，用于查询相邻顶点与检查收缩的连接条件
+        let mut hem = HalfEdgeMesh::new(self);
+
+        // v 的所有相邻顶点
+        macro_rules! neighbors {
+            ($v:expr) => {{
+                let mut nb: Vec<_> = match &hem {
+                    Some(m) => m.one_ring($v),
+                    None => head[$v]
+                        .iter()
+                        .filter(|&&i| is_valid_tri!(i))
+                        .flat_map(|&i| [tri[i].0, tri[i].1, tri[i].2])
+                        .filter(|&x| x != $v)
+                        .collect(),
+                };
+                nb.sort_unstable();
+                nb.dedup();
+                nb
+            }};
+        }
+
+        // 收缩到 v 之后颜色误差最小的颜色
+        macro_rules! new_color {
+            ($v1:expr, $v2:expr, $v:expr) => {{
+                (cq[$v1] + cq[$v2]).optimal($v).unwrap_or_else(|| {
+                    let t = edge_param(pos[$v1], pos[$v2], $v);
+                    color[$v1] * (1.0 - t) + color[$v2] * t
+                })
+            }};
+        }
+
+        // 边 (v1, v2) 收缩后的新位置与代价，不能收缩时为 None；
+        // 入堆时不检查面的翻转，出堆时再检查，以减少收集周围的面的次数
+        macro_rules! place {
+            ($v1:expr, $v2:expr, $check:expr) => {{
+                let e = pos[$v1] - pos[$v2];
+                if e.len2() >= DIST_EPS || !allowed!($v1, $v2) {
+                    None
+                } else {
+                    let mut quadric = q[$v1] + q[$v2];
+                    if !qc.is_empty() {
+                        quadric += qc[$v1] + qc[$v2];
+                    }
+                    let around = || {
+                        let mut fs: Vec<_> =
+                            head[$v1].iter().chain(head[$v2].iter()).cloned().collect();
+                        fs.retain(|&i| is_valid_tri!(i));
+                        fs.sort_unstable();
+                        fs.dedup();
+                        fs.into_iter().map(|i| tri[i]).collect()
+                    };
+                    let ctx = CollapseContext::new($v1, $v2, &pos, quadric, &around);
+                    // 使周围的面翻转或退化的位置不可取，误差矩阵的代价在平面内移动时为零，不能发现翻转
+                    let flips = |x: Vct| {
+                        $check
+                            && ctx
+                                .moved_normals(x)
+                                .iter()
+                                .any(|(n0, n1)| n0.len2() > 0.0 && n0.dot(*n1) <= 0.0)
+                    };
+                    let v = match (Feature::new(fadj[$v1].len()), Feature::new(fadj[$v2].len())) {
+                        (Feature::Smooth, Feature::Smooth) => {
+                            placement
+                                .candidates(&ctx)
+                                .into_iter()
+                                .map(|x| (if flips(x) { Flt::INFINITY } else { cost.cost(&ctx, x) }, x))
+                                .min_by(|a, b| a.0.total_cmp(&b.0))
+                                .map_or(ctx.mid(), |b| b.1)
+                        },
+                        (Feature::Smooth, _) | (_, Feature::Corner) => pos[$v2],
+                        (_, Feature::Smooth) | (Feature::Corner, _) => pos[$v1],
+                        _ => {
+                            let t = (ctx.optimal() - pos[$v1]).dot(-e) / e.len2();
+                            pos[$v1] - e * if t.is_nan() { 0.5 } else { t.clamp(0.0, 1.0) }
+                        },
+                    };
+                    let mut cost = if flips(v) { Flt::INFINITY } else { cost.cost(&ctx, v) };
+                    if !skin.is_empty() && skin[$v1].dominant() != skin[$v2].dominant() {
+                        cost += opts.skin_penalty;
+                    }
+                    if !cq.is_empty() {
+                        cost += (cq[$v1] + cq[$v2]).error(v, new_color!($v1, $v2, v)) * cw;
+                    }
+                    Some((v, cost)).filter(|_| cost < COST_EPS)
+                }
+            }};
+        }
+
+        macro_rules! push_edge {
+            ($v1:expr, $v2:expr) => {
+                if let Some((_, cost)) = place!($v1, $v2, false) {
+                    heap.push(State::new(cost, $v1, $v2, ver[$v1], ver[$v2]));
+                }
+            };
+        }
+
+        // 每条边只入堆一次
+        let init = par_map(n, threads, |range| {
+            let mut ret = vec![];
+            for v in range {
+                for u in neighbors!(v).into_iter().filter(|&u| v < u) {
+                    if let Some((_, cost)) = place!(v, u, false) {
+                        ret.push(State::new(cost, v, u, 0, 0));
+                    }
+                }
+            }
+            ret
+        });
+        heap.extend(init);
+
+        let target = (self.tri.len() as Flt * (1.0 - opts.ratio)).max(0.0) as usize;
+        let interval = opts.progress_interval.max(1);
+        let (mut removed, mut error, mut next) = (0, carry.error, interval);
+        macro_rules! report {
+            () => {
+                match &opts.observer {
+                    Some(ob) => ob.progress(&Progress {
+                        faces: self.tri.len() - removed,
+                        target: self.tri.len() - target,
+                        error,
+                    }),
+                    None => Control::Continue,
+                }
+            };
+        }
+        let (mut cancelled, mut collapses) = (false, vec![]);
+        while removed < target {
+            let s = match heap.pop() {
+                Some(s) => s,
+                None => break,
+            };
+            let (v1, v2) = (s.v1 as usize, s.v2 as usize);
+            if dele[v1] || dele[v2] || ver[v1] != s.ver1 || ver[v2] != s.ver2 {
+                continue;
+            }
+            let v = match place!(v1, v2, true) {
+                Some((_, cost)) if opts.max_error.is_some_and(|m| cost > m) => break,
+                Some((v, cost)) => {
+                    // 不满足连接条件的收缩会产生非流形的结果
+                    if let Some(m) = &mut hem {
+                        let h = m.find_edge(v1, v2).or_else(|| m.find_edge(v2, v1));
+                        if h.and_then(|h| m.collapse_to(h, v1, v)).is_none() {
+                            continue;
+                        }
+                    }
+                    error = cost.max(error);
+                    collapses.push(Collapse { v1, v2, pos: v, cost });
+                    v
+                },
+                None => continue,
+            };
+
+            // 同时含 v1、v2 的面被删除，其余的面记下移动前的误差矩阵
+            let (mut faces, mut touched) = (vec![], vec![v1]);
+            for &i in &head[v1] {
+                if is_valid_tri!(i) {
+                    let (a, b, c) = tri[i];
+                    let qk = Quadric::triangle(pos[a], pos[b], pos[c]);
+                    let ck = if cq.is_empty() { ColorQuadric::default() } else { face_cq!(i) };
+                    let fw = face_w!(i);
+                    if !in_tri!(i, v2) {
+                        faces.push((i, qk, ck, fw));
+                    } else {
+                        let v3 = a + b + c - v1 - v2;
+                        touched.push(v3);
+                        let k = fw[slot!(i, v3)] * w[v3];
+                        q[v3] -= qk * k;
+                        if !cq.is_empty() {
+                            cq[v3] -= ck * k;
+                        }
+                        removed += 1;
+                    }
+                }
+            }
+            for &i in &head[v2] {
+                if is_valid_tri!(i) && !in_tri!(i, v1) {
+                    let (a, b, c) = tri[i];
+                    let ck = if cq.is_empty() { ColorQuadric::default() } else { face_cq!(i) };
+                    faces.push((i, Quadric::triangle(pos[a], pos[b], pos[c]), ck, face_w!(i)));
+                }
+            }
+
+            if !skin.is_empty() {
+                skin[v1] = skin[v1].lerp(&skin[v2], edge_param(pos[v1], pos[v2], v));
+            }
+            if !cq.is_empty() {
+                color[v1] = new_color!(v1, v2, v);
+                cq[v1] = ColorQuadric::default();
+            }
+            dele[v2] = true;
+            pos[v1] = v;
+            w[v1] = w[v1].max(w[v2]);
+            q[v1] = Quadric::default();
+            if !qc.is_empty() {
+                let qk = qc[v2];
+                qc[v1] += qk;
+            }
+            if !fadj[v1].is_empty() || !fadj[v2].is_empty() {
+                let mut fv: Vec<_> = fadj[v1].iter().chain(fadj[v2].iter()).cloned().collect();
+                fv.retain(|&x| x != v1 && x != v2);
+                fv.sort_unstable();
+                fv.dedup();
+                fv.iter().for_each(|&x| {
+                    fadj[x].retain(|&y| y != v1 && y != v2);
+                    fadj[x].push(v1);
+                });
+                fadj[v1] = fv;
+                fadj[v2] = vec![];
+            }
+
+            for &(i, qo, co, fo) in &faces {
+                let t = &mut tri[i];
+                for x in [&mut t.0, &mut t.1, &mut t.2] {
+                    if *x == v2 {
+                        *x = v1;
+                    }
+                }
+                let (a, b, c) = tri[i];
+                touched.extend_from_slice(&[a, b, c]);
+                let qk = Quadric::triangle(pos[a], pos[b], pos[c]);
+                let fk = face_w!(i);
+                for (k, &x) in [a, b, c].iter().enumerate() {
+                    if x == v1 {
+                        q[x] += qk * (fk[k] * w[x]);
+                    } else {
+                        q[x] += (qk * fk[k] - qo * fo[k]) * w[x];
+                    }
+                }
+                if !cq.is_empty() {
+                    let ck = face_cq!(i);
+                    for (k, &x) in [a, b, c].iter().enumerate() {
+                        cq[x] += if x == v1 { ck * fk[k] } else { ck * fk[k] - co * fo[k] } * w[x];
+                    }
+                }
+            }
+            head[v1] = faces.into_iter().map(|(i, _, _, _)| i).collect();
+            head[v2] = vec![];
+            // 这些顶点的边都要按新的代价重新入堆，两端都改变的边只入堆一次
+            touched.sort_unstable();
+            touched.dedup();
+            touched.iter().for_each(|&x| ver[x] += 1);
+            for &x in &touched {
+                for u in neighbors!(x) {
+                    if x < u || touched.binary_search(&u).is_err() {
+                        push_edge!(x, u);
+                    }
+                }
+            }
+            if removed >= next {
+                next = removed + interval;
+                if report!() == Control::Cancel {
+                    cancelled = true;
+                    break;
+                }
+            }
+        }
+        if !cancelled {
+            report!();
+        }
+
+        let (mut new_pos, mut new_tri, mut src) = (vec![], vec![], vec![]);
+        let mut id = vec![usize::MAX; n];
+        macro_rules! gg {
+            ($i:expr) => {{
+                if id[$i] == usize::MAX {
+                    id[$i] = new_pos.len();
+                    new_pos.push(pos[$i]);
+                    src.push($i);
+                }
+                id[$i]
+            }};
+        }
+        for i in 0..tri.len() {
+            if is_valid_tri!(i) {
+                new_tri.push((gg!(tri[i].0), gg!(tri[i].1), gg!(tri[i].2)));
+            }
+        }
+        let mut ret = Self::from_raw(new_pos, new_tri);
+        if !skin.is_empty() {
+            ret.skin = src.iter().map(|&v| skin[v]).collect();
+        }
+        if !color.is_empty() {
+            ret.color = src.iter().map(|&v| color[v]).collect();
+        }
+        let carry = Carry { weights: src.iter().map(|&v| w[v]).collect(), error };
+        (ret, src, collapses, carry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_count() {
+        let mesh = Mesh::new("assets/block.obj");
+        assert_eq!(mesh.simplify(1.0).tri.len(), mesh.tri.len());
+        let out = mesh.simplify(0.25);
+        let target = mesh.tri.len() - (mesh.tri.len() as Flt * 0.75) as usize;
+        assert!(out.tri.len() <= target && out.tri.len() + 2 >= target);
+    }
+
+    #[test]
+    fn normals() {
+        let mut mesh = Mesh::new("assets/block.obj");
+        assert!(!mesh.simplify(0.5).has_normals());
+        mesh.compute_normals(NormalWeight::Angle, Some(NORMAL_CREASE));
+        let out = mesh.simplify(0.5);
+        assert!(out.has_normals() && out.nrm_tri.len() == out.tri.len());
+        assert!(out.nrm.iter().all(|n| (n.len() - 1.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn single_precision() {
+        let mesh = Mesh::<f32>::load("assets/block.obj");
+        let out = mesh.simplify(0.25);
+        let orig = mesh.cast::<Flt>();
+        let expect = orig.simplify(0.25);
+        // f32 的舍入会改变收缩的顺序，只要求面数与误差和 f64 的结果相当
+        assert!(out.tri.len().abs_diff(expect.tri.len()) <= expect.tri.len() / 100);
+        let diag = orig.bbox().size().len();
+        let (d, e) = (out.cast::<Flt>().hausdorff(&orig, 1).max, expect.hausdorff(&orig, 1).max);
+        assert!(d < e * 1.5 + diag * 1e-4 && d < diag * 1e-3);
+        let n = out.vertex_normals(crate::NormalWeight::Area);
+        assert!(n.iter().all(|n| (n.len() - 1.0).abs() < 1e-4));
+    }
+
+    #[test]
+    fn quadric_weight() {
+        let mesh = Mesh::new("assets/block.obj");
+        let target = mesh.simplify(0.2).tri.len();
+        for weight in [NormalWeight::Area, NormalWeight::Angle] {
+            let opts = SimplifyOptions { ratio: 0.2, quadric_weight: weight, ..Default::default() };
+            assert!(mesh.simplify_with(&opts).tri.len().abs_diff(target) <= 2);
+        }
+        // 缩放 2 的幂不引入舍入误差，归一化之后的结果与收缩代价完全一致
+        let mut big = mesh.clone();
+        big.pos.iter_mut().for_each(|p| *p *= 1024.0);
+        let opts = SimplifyOptions { ratio: 0.2, normalize: true, ..Default::default() };
+        let (a, b) = (mesh.simplify_detailed(&opts), big.simplify_detailed(&opts));
+        assert_eq!(a.mesh.tri, b.mesh.tri);
+        assert!(a.collapses.iter().zip(b.collapses.iter()).all(|(x, y)| x.cost == y.cost));
+        assert!(a.mesh.pos.iter().zip(b.mesh.pos.iter()).all(|(&p, &q)| p * 1024.0 == q));
+    }
+
+    #[test]
+    fn corners_stay() {
+        let mesh = Mesh::new("assets/cube.obj");
+        assert!(mesh.simplify(0.1).tri.len() < mesh.tri.len());
+        let opts = SimplifyOptions { ratio: 0.1, feature_angle: Some(30.0), ..Default::default() };
+        let out = mesh.simplify_with(&opts);
+        assert_eq!(out.tri.len(), mesh.tri.len());
+        out.pos.iter().for_each(|p| assert!(mesh.pos.contains(p)));
+    }
+
+    #[test]
+    fn crease_edges() {
+        let mesh = Mesh::new("assets/block.obj");
+        let crease: Vec<_> = mesh.tri.iter().take(20).map(|&(a, b, _)| (a, b)).collect();
+        let opts =
+            SimplifyOptions { ratio: 0.2, crease_edges: crease.clone(), ..Default::default() };
+        let out = mesh.simplify_with(&opts);
+        assert!(out.tri.len() < mesh.tri.len() / 2);
+        let corner = |v: usize| crease.iter().filter(|&&(a, b)| a == v || b == v).count() != 2;
+        crease.iter().flat_map(|&(a, b)| vec![a, b]).filter(|&v| corner(v)).for_each(|v| {
+            assert!(out.pos.contains(&mesh.pos[v]));
+        });
+
+        // 文件中的下标从 1 开始，被拆开的顶点的每一份都参与
+        let source = VertexSource { src: vec![0, 1, 2, 0], count: 3 };
+        let mut opts = SimplifyOptions::default();
+        opts.add_crease_edges(&[(1, 2), (3, 2)], &source).unwrap();
+        assert_eq!(opts.crease_edges, vec![(0, 1), (3, 1), (2, 1)]);
+        assert!(opts.add_crease_edges(&[(0, 1)], &source).is_err());
+        assert!(opts.add_crease_edges(&[(1, 4)], &source).is_err());
+        assert!(opts.validate(4).is_ok() && opts.validate(3).is_err());
+        let path = std::env::temp_dir().join("mesh_simplification_creases.txt");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "# creases\n1 2\n\n3 2 # rim\n").unwrap();
+        let mut opts = SimplifyOptions::default();
+        opts.load_crease_edges(path, &source).unwrap();
+        assert_eq!(opts.crease_edges, vec![(0, 1), (3, 1), (2, 1)]);
+        std::fs::write(path, "1 2\n1 4\n").unwrap();
+        let e = opts.load_crease_edges(path, &source).unwrap_err();
+        assert!(e.starts_with(&format!("{}:2: ", path)), "{}", e);
+    }
+
+    #[test]
+    fn locked() {
+        let mesh = Mesh::new("assets/block.obj");
+        let locked: Vec<_> = mesh.pos.iter().map(|p| p.z > 10.0).collect();
+        let opts = SimplifyOptions { ratio: 0.1, locked: locked.clone(), ..Default::default() };
+        let out = mesh.simplify_with(&opts);
+        assert!(out.tri.len() < mesh.tri.len() / 2);
+        let faces: Vec<_> =
+            out.tri.iter().map(|&(a, b, c)| (out.pos[a], out.pos[b], out.pos[c])).collect();
+        mesh.tri.iter().filter(|&&(a, b, c)| locked[a] && locked[b] && locked[c]).for_each(
+            |&(a, b, c)| assert!(faces.contains(&(mesh.pos[a], mesh.pos[b], mesh.pos[c]))),
+        );
+    }
+
+    #[test]
+    fn vertex_marks() {
+        let path = std::env::temp_dir().join("mesh_simplification_marks.txt");
+        let path = path.to_str().unwrap();
+        let source = VertexSource { src: vec![0, 1, 2, 0], count: 3 };
+        let load = |s: &str| {
+            std::fs::write(path, s).unwrap();
+            let mut opts = SimplifyOptions::default();
+            opts.load_vertex_marks(path, &source).map(|_| (opts.weights, opts.locked))
+        };
+        let (w, l) = load("# marks\n1 2.5\n\n3 lock # anchor\n").unwrap();
+        assert_eq!((w, l), (vec![2.5, 1.0, 1.0, 2.5], vec![false, false, true, false]));
+        for (s, line) in
+            [("1 1\n0 1\n", 2), ("4 lock\n", 1), ("x 1\n", 1), ("2 -1\n", 1), ("2\n", 1)]
+        {
+            let e = load(s).unwrap_err();
+            assert!(e.starts_with(&format!("{}:{}: ", path, line)), "{}", e);
+        }
+        assert!(SimplifyOptions::default().load_vertex_marks("/nonexistent", &source).is_err());
+    }
+
+    // 需要足够光滑的曲面才能比较两种策略的体积，较慢，用 cargo test -- --ignored 运行
+    #[test]
+    #[ignore]
+    fn volume() {
+        let volume = |m: &Mesh| {
+            m.tri.iter().map(|&(a, b, c)| m.pos[a].dot(m.pos[b] % m.pos[c]) / 6.0).sum::<Flt>()
+        };
+        let mesh = Mesh::new("assets/sphere.obj");
+        let opts = SimplifyOptions { ratio: 0.02, ..Default::default() };
+        let quadric = volume(&mesh.simplify_with(&opts));
+        let opts = SimplifyOptions { strategy: Strategy::Volume, ..opts };
+        let out = mesh.simplify_with(&opts);
+        assert!(out.tri.len() < mesh.tri.len() / 20);
+        assert!((volume(&out) - volume(&mesh)).abs() < (quadric - volume(&mesh)).abs());
+    }
+
+    #[test]
+    fn endpoint_placement() {
+        let mesh = Mesh::new("assets/block.obj");
+        let opts = SimplifyOptions {
+            ratio: 0.2,
+            placement: PlacementPolicy::Endpoint,
+            ..Default::default()
+        };
+        let out = mesh.simplify_with(&opts);
+        assert!(out.tri.len() < mesh.tri.len() / 4);
+        out.pos.iter().for_each(|p| assert!(mesh.pos.contains(p)));
+    }
+
+    #[test]
+    fn max_error() {
+        let mesh = Mesh::new("assets/block.obj");
+        let opts = SimplifyOptions { ratio: 0.0, max_error: Some(1e-6), ..Default::default() };
+        let ret = mesh.simplify_detailed(&opts);
+        assert!(!ret.mesh.tri.is_empty() && ret.mesh.tri.len() < mesh.tri.len());
+        assert!(ret.collapses.iter().all(|c| c.cost <= 1e-6));
+    }
+
+    #[test]
+    fn history() {
+        let mesh = Mesh::new("assets/block.obj");
+        let ret = mesh.simplify_detailed(&SimplifyOptions { ratio: 0.2, ..Default::default() });
+        assert!(HalfEdgeMesh::new(&ret.mesh).is_some());
+        let mut pos = mesh.pos.clone();
+        let mut dele = vec![false; pos.len()];
+        ret.collapses.iter().for_each(|c| {
+            assert!(!dele[c.v1] && !dele[c.v2]);
+            dele[c.v2] = true;
+            pos[c.v1] = c.pos;
+        });
+        assert_eq!(ret.vertex_map.len(), mesh.pos.len());
+        ret.vertex_map.iter().enumerate().for_each(|(v, &j)| {
+            if !dele[v] {
+                assert_eq!(ret.mesh.pos[j], pos[v]);
+            }
+        });
+        let mut hit = vec![false; ret.mesh.pos.len()];
+        ret.vertex_map.iter().for_each(|&j| hit[j] = true);
+        assert!(hit.iter().all(|&h| h));
+    }
+}
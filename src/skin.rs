@@ -0,0 +1,91 @@
+use crate::Flt;
+
+// 最多受 4 根骨骼影响，权重为 0 的骨骼不起作用
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Skin {
+    pub bones: [usize; 4],
+    pub weights: [Flt; 4],
+}
+
+impl Skin {
+    pub fn new(bones: [usize; 4], weights: [Flt; 4]) -> Self {
+        Self { bones, weights }
+    }
+
+    // 权重最大的骨骼
+    pub fn dominant(&self) -> usize {
+        let i = (0..4).fold(0, |a, b| if self.weights[b] > self.weights[a] { b } else { a });
+        self.bones[i]
+    }
+
+    // 按 (1 - t, t) 混合两组权重，同一骨骼的权重相加后保留最大的 4 个并归一化；
+    // t 为 0 或 1 时直接取对应端点的权重
+    pub fn lerp(&self, other: &Self, t: Flt) -> Self {
+        if t <= 0.0 {
+            return *self;
+        } else if t >= 1.0 {
+            return *other;
+        }
+        let mut acc: Vec<(usize, Flt)> = vec![];
+        for (s, k) in [(self, 1.0 - t), (other, t)] {
+            for i in (0..4).filter(|&i| s.weights[i] > 0.0) {
+                match acc.iter_mut().find(|(b, _)| *b == s.bones[i]) {
+                    Some(a) => a.1 += s.weights[i] * k,
+                    None => acc.push((s.bones[i], s.weights[i] * k)),
+                }
+            }
+        }
+        acc.sort_by(|a, b| b.1.total_cmp(&a.1));
+        acc.truncate(4);
+        let sum: Flt = acc.iter().map(|a| a.1).sum();
+        let mut ret = Self::default();
+        acc.iter().enumerate().for_each(|(i, &(b, x))| {
+            ret.bones[i] = b;
+            ret.weights[i] = if sum > 0.0 { x / sum } else { 0.0 };
+        });
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mesh, PlacementPolicy, SimplifyOptions};
+
+    #[test]
+    fn lerp() {
+        let a = Skin::new([1, 2, 0, 0], [0.75, 0.25, 0.0, 0.0]);
+        let b = Skin::new([2, 3, 4, 5], [0.4, 0.3, 0.2, 0.1]);
+        assert_eq!(a.lerp(&b, 0.0), a);
+        assert_eq!(a.lerp(&b, 1.0), b);
+        let c = a.lerp(&b, 0.5);
+        assert_eq!(c.dominant(), 1);
+        assert_eq!(c.bones[..3], [1, 2, 3]);
+        assert!((c.weights.iter().sum::<Flt>() - 1.0).abs() < crate::EPS);
+    }
+
+    #[test]
+    fn simplify_skin() {
+        let mut mesh = Mesh::new("assets/block.obj");
+        mesh.skin = mesh
+            .pos
+            .iter()
+            .map(|p| {
+                let t = (p.z / 10.0).clamp(-1.0, 1.0) * 0.5 + 0.5;
+                Skin::new([0, 1, 0, 0], [1.0 - t, t, 0.0, 0.0])
+            })
+            .collect();
+        let opts = SimplifyOptions { ratio: 0.1, skin_penalty: 1e3, ..Default::default() };
+        let ret = mesh.simplify_detailed(&opts);
+        assert_eq!(ret.mesh.skin.len(), ret.mesh.pos.len());
+        ret.collapses.iter().for_each(|c| {
+            assert_eq!(mesh.skin[c.v1].dominant(), mesh.skin[c.v2].dominant());
+        });
+        let opts = SimplifyOptions { placement: PlacementPolicy::Endpoint, ..opts };
+        let ret = mesh.simplify_detailed(&opts);
+        ret.mesh.pos.iter().zip(ret.mesh.skin.iter()).for_each(|(p, s)| {
+            let v = mesh.pos.iter().position(|q| q == p).unwrap();
+            assert_eq!(*s, mesh.skin[v]);
+        });
+    }
+}
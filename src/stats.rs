@@ -0,0 +1,447 @@
+use crate::normal::{angle, find};
+use crate::parallel::par_map;
+use crate::{Aabb, Flt, Mat, Mesh, Vct, PI};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshStats {
+    pub vertices: usize,
+    pub faces: usize,
+    pub edges: usize,
+    // 只属于一个面的边与属于三个及以上面的边
+    pub boundary_edges: usize,
+    pub non_manifold_edges: usize,
+    // 有重复顶点或面积为 0 的面
+    pub degenerate_faces: usize,
+    pub unreferenced_vertices: usize,
+    pub components: usize,
+    pub bbox: Aabb,
+    pub area: Flt,
+    pub volume: Flt,
+    pub center_of_mass: Vct,
+    pub edge_length: EdgeLengths,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EdgeLengths {
+    pub count: usize,
+    pub min: Flt,
+    pub max: Flt,
+    pub mean: Flt,
+    pub stddev: Flt,
+}
+
+// 第 i 格统计 [bounds[i], bounds[i + 1]) 中的值，超出两端的值计入第一格或最后一格；
+// new 在边界少于两个时补上 -inf 或 inf，至少有一格
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub bounds: Vec<Flt>,
+    pub counts: Vec<usize>,
+}
+
+// 三角形质量的分布：长宽比为外接圆半径与两倍内切圆半径之比（正三角形为 1），最小内角为角度制
+#[derive(Clone, Debug, PartialEq)]
+pub struct TriangleQuality {
+    pub aspect: Histogram,
+    pub min_angle: Histogram,
+    pub max_aspect: Flt,
+    pub smallest_angle: Flt,
+}
+
+// 单向的点到曲面距离：from 上的采样点到 to 的最近距离
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Distance {
+    pub mean: Flt,
+    pub rms: Flt,
+    pub max: Flt,
+}
+
+impl fmt::Display for MeshStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "vertices:              {}", self.vertices)?;
+        writeln!(f, "faces:                 {}", self.faces)?;
+        writeln!(f, "edges:                 {}", self.edges)?;
+        writeln!(f, "boundary edges:        {}", self.boundary_edges)?;
+        writeln!(f, "non-manifold edges:    {}", self.non_manifold_edges)?;
+        writeln!(f, "degenerate faces:      {}", self.degenerate_faces)?;
+        writeln!(f, "unreferenced vertices: {}", self.unreferenced_vertices)?;
+        writeln!(f, "components:            {}", self.components)?;
+        writeln!(f, "euler characteristic:  {}", self.euler())?;
+        writeln!(f, "bounding box:          {}", self.bbox)?;
+        writeln!(f, "surface area:          {}", self.area)?;
+        writeln!(f, "volume:                {}", self.volume)?;
+        writeln!(f, "center of mass:        {}", self.center_of_mass)?;
+        write!(f, "edge length:           {}", self.edge_length)
+    }
+}
+
+impl fmt::Display for EdgeLengths {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "min {:.6e}, max {:.6e}, mean {:.6e}, stddev {:.6e}",
+            self.min, self.max, self.mean, self.stddev
+        )
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.total().max(1) as Flt;
+        for (i, &n) in self.counts.iter().enumerate() {
+            let range = format!("[{}, {})", self.bounds[i], self.bounds[i + 1]);
+            let end = if i + 1 == self.counts.len() { "" } else { "\n" };
+            write!(f, "  {:<12} {:>9} {:>6.2}%{}", range, n, n as Flt / total * 100.0, end)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TriangleQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "aspect ratio:          max {:.4}", self.max_aspect)?;
+        writeln!(f, "{}", self.aspect)?;
+        writeln!(f, "min angle:             min {:.4}", self.smallest_angle)?;
+        write!(f, "{}", self.min_angle)
+    }
+}
+
+impl Histogram {
+    pub fn new(mut bounds: Vec<Flt>) -> Self {
+        if bounds.is_empty() {
+            bounds.push(Flt::NEG_INFINITY);
+        }
+        if bounds.len() == 1 {
+            bounds.push(Flt::INFINITY);
+        }
+        let counts = vec![0; bounds.len() - 1];
+        Self { bounds, counts }
+    }
+
+    pub fn add(&mut self, x: Flt) {
+        let i = self.bounds[1..].iter().take_while(|&&b| x >= b).count();
+        let last = self.counts.len() - 1;
+        self.counts[i.min(last)] += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mean {:.6e}, rms {:.6e}, max {:.6e}", self.mean, self.rms, self.max)
+    }
+}
+
+impl MeshStats {
+    pub fn euler(&self) -> i64 {
+        (self.vertices - self.unreferenced_vertices) as i64 - self.edges as i64 + self.faces as i64
+    }
+}
+
+// 退化的三角形为无穷大
+pub fn aspect_ratio(a: Vct, b: Vct, c: Vct) -> Flt {
+    let (x, y, z) = ((b - c).len(), (c - a).len(), (a - b).len());
+    let area = ((b - a) % (c - a)).len() * 0.5;
+    if area <= 0.0 {
+        return Flt::INFINITY;
+    }
+    x * y * z * (x + y + z) / (16.0 * area * area)
+}
+
+// 角度制，退化的三角形为 0
+pub fn min_angle(a: Vct, b: Vct, c: Vct) -> Flt {
+    if ((b - a) % (c - a)).len2() <= 0.0 {
+        return 0.0;
+    }
+    angle(b - a, c - a).min(angle(c - b, a - b)).min(angle(a - c, b - c)) * 180.0 / PI
+}
+
+// 三角形 abc 上离 p 最近的点
+pub(crate) fn closest_on_tri(p: Vct, a: Vct, b: Vct, c: Vct) -> Vct {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    // 投影落在三角形内部，va + vb + vc 为 0 时三角形退化，已在上面某个分支返回
+    let denom = 1.0 / (va + vb + vc);
+    a + ab * (vb * denom) + ac * (vc * denom)
+}
+
+impl Mesh {
+    pub fn stats(&self) -> MeshStats {
+        let edge_faces = self.edge_faces();
+        let mut used = vec![false; self.pos.len()];
+        let mut f: Vec<_> = (0..self.pos.len()).collect();
+        let mut degenerate = 0;
+        self.tri.iter().for_each(|&(a, b, c)| {
+            [a, b, c].iter().for_each(|&x| used[x] = true);
+            let (ra, rb) = (find(&mut f, a), find(&mut f, b));
+            f[ra] = rb;
+            let (rb, rc) = (find(&mut f, b), find(&mut f, c));
+            f[rb] = rc;
+            let (pa, pb, pc) = (self.pos[a], self.pos[b], self.pos[c]);
+            let n = (pb - pa) % (pc - pa);
+            if a == b || b == c || c == a || n.len2() <= 0.0 {
+                degenerate += 1;
+            }
+        });
+        MeshStats {
+            vertices: self.pos.len(),
+            faces: self.tri.len(),
+            edges: edge_faces.len(),
+            boundary_edges: edge_faces.values().filter(|f| f.len() == 1).count(),
+            non_manifold_edges: edge_faces.values().filter(|f| f.len() > 2).count(),
+            degenerate_faces: degenerate,
+            unreferenced_vertices: used.iter().filter(|&&u| !u).count(),
+            components: (0..self.pos.len()).filter(|&v| used[v] && find(&mut f, v) == v).count(),
+            bbox: self.bbox(),
+            area: self.area(),
+            volume: self.signed_volume(),
+            center_of_mass: self.center_of_mass(),
+            edge_length: self.edge_lengths(),
+        }
+    }
+
+    fn face_area(&self, (a, b, c): (usize, usize, usize)) -> Flt {
+        ((self.pos[b] - self.pos[a]) % (self.pos[c] - self.pos[a])).len() * 0.5
+    }
+
+    pub fn area(&self) -> Flt {
+        self.tri.iter().map(|&t| self.face_area(t)).sum()
+    }
+
+    // 面朝外的封闭网格为正
+    pub fn signed_volume(&self) -> Flt {
+        self.tri.iter().map(|&(a, b, c)| self.pos[a].dot(self.pos[b] % self.pos[c]) / 6.0).sum()
+    }
+
+    // 按面积加权的表面重心，没有面积时取顶点的平均
+    pub fn centroid(&self) -> Vct {
+        let area = self.area();
+        if area > 0.0 {
+            self.tri
+                .iter()
+                .map(|&(a, b, c)| {
+                    (self.pos[a] + self.pos[b] + self.pos[c]) * (self.face_area((a, b, c)) / 3.0)
+                })
+                .fold(Vct::zero(), |s, x| s + x)
+                / area
+        } else {
+            self.pos.iter().fold(Vct::zero(), |s, &x| s + x) / self.pos.len().max(1) as Flt
+        }
+    }
+
+    // 以原点为顶点、每个面为底的四面体的有向体积、一阶矩与二阶矩（协方差）之和，
+    // 网格封闭时即为所围实体（密度为 1）的体积、质量中心乘以体积与关于原点的二阶矩
+    fn moments(&self) -> (Flt, Vct, Mat) {
+        let (mut vol, mut first, mut second) = (0.0, Vct::zero(), Mat::default());
+        self.tri.iter().for_each(|&(a, b, c)| {
+            let (pa, pb, pc) = (self.pos[a], self.pos[b], self.pos[c]);
+            let d = pa.dot(pb % pc);
+            let s = pa + pb + pc;
+            vol += d / 6.0;
+            first += s * (d / 24.0);
+            for i in 0..3 {
+                for j in 0..3 {
+                    let p = pa[i] * pa[j] + pb[i] * pb[j] + pc[i] * pc[j] + s[i] * s[j];
+                    second[i][j] += p * d / 120.0;
+                }
+            }
+        });
+        (vol, first, second)
+    }
+
+    // 所围实体的质心，体积为 0 时（不封闭或退化）退回表面重心
+    pub fn center_of_mass(&self) -> Vct {
+        match self.moments() {
+            (vol, first, _) if vol != 0.0 => first / vol,
+            _ => self.centroid(),
+        }
+    }
+
+    // 密度为 1 时关于质心的惯性张量，在结果的左上角 3x3 部分
+    pub fn inertia(&self) -> Mat {
+        let (vol, first, mut c) = self.moments();
+        if vol != 0.0 {
+            // 平行轴定理，把二阶矩移到质心
+            let g = first / vol;
+            for i in 0..3 {
+                for j in 0..3 {
+                    c[i][j] -= vol * g[i] * g[j];
+                }
+            }
+        }
+        let trace = c[0][0] + c[1][1] + c[2][2];
+        let mut ret = Mat::default();
+        for i in 0..3 {
+            for j in 0..3 {
+                ret[i][j] = if i == j { trace } else { 0.0 } - c[i][j];
+            }
+        }
+        ret
+    }
+
+    // 每条边只统计一次
+    pub fn edge_lengths(&self) -> EdgeLengths {
+        let d: Vec<_> =
+            self.edge_faces().keys().map(|&(u, v)| self.pos[u].distance(self.pos[v])).collect();
+        if d.is_empty() {
+            return EdgeLengths::default();
+        }
+        let n = d.len() as Flt;
+        let mean = d.iter().sum::<Flt>() / n;
+        EdgeLengths {
+            count: d.len(),
+            min: d.iter().cloned().fold(Flt::INFINITY, Flt::min),
+            max: d.iter().cloned().fold(0.0, Flt::max),
+            mean,
+            stddev: (d.iter().map(|x| (x - mean) * (x - mean)).sum::<Flt>() / n).sqrt(),
+        }
+    }
+
+    pub fn triangle_quality(&self) -> TriangleQuality {
+        let mut q = TriangleQuality {
+            aspect: Histogram::new(vec![1.0, 1.5, 2.0, 3.0, 5.0, 10.0, Flt::INFINITY]),
+            min_angle: Histogram::new((0..=6).map(|i| i as Flt * 10.0).collect()),
+            max_aspect: if self.tri.is_empty() { 0.0 } else { 1.0 },
+            smallest_angle: if self.tri.is_empty() { 0.0 } else { 60.0 },
+        };
+        self.tri.iter().for_each(|&(a, b, c)| {
+            let (pa, pb, pc) = (self.pos[a], self.pos[b], self.pos[c]);
+            let (r, m) = (aspect_ratio(pa, pb, pc), min_angle(pa, pb, pc));
+            q.aspect.add(r);
+            q.min_angle.add(m);
+            q.max_aspect = q.max_aspect.max(r);
+            q.smallest_angle = q.smallest_angle.min(m);
+        });
+        q
+    }
+
+    // 以本网格的顶点与面重心为采样点，用 other 的 BVH 逐个求到 other 表面的最近距离
+    pub fn distance_to(&self, other: &Mesh, threads: usize) -> Distance {
+        let samples: Vec<_> = self
+            .pos
+            .iter()
+            .cloned()
+            .chain(
+                self.tri.iter().map(|&(a, b, c)| (self.pos[a] + self.pos[b] + self.pos[c]) / 3.0),
+            )
+            .collect();
+        if samples.is_empty() || other.tri.is_empty() {
+            return Distance::default();
+        }
+        let bvh = other.bvh();
+        let d = par_map(samples.len(), threads, |range| {
+            range.map(|i| bvh.closest_point(samples[i]).map_or(0.0, |n| n.dist)).collect()
+        });
+        let n = d.len() as Flt;
+        Distance {
+            mean: d.iter().sum::<Flt>() / n,
+            rms: (d.iter().map(|x| x * x).sum::<Flt>() / n).sqrt(),
+            max: d.iter().cloned().fold(0.0, Flt::max),
+        }
+    }
+
+    // 双向距离中较大的一个，max 即为 Hausdorff 距离的近似
+    pub fn hausdorff(&self, other: &Mesh, threads: usize) -> Distance {
+        let (a, b) = (self.distance_to(other, threads), other.distance_to(self, threads));
+        Distance { mean: a.mean.max(b.mean), rms: a.rms.max(b.rms), max: a.max.max(b.max) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EPS;
+
+    #[test]
+    fn stats() {
+        let s = Mesh::new("assets/cube.obj").stats();
+        assert_eq!((s.boundary_edges, s.non_manifold_edges, s.degenerate_faces), (0, 0, 0));
+        assert_eq!((s.components, s.euler()), (1, 2));
+        let d = s.bbox.size();
+        assert!((s.area - 2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)).abs() < EPS);
+        assert!((s.volume - d.x * d.y * d.z).abs() < EPS);
+    }
+
+    #[test]
+    fn measure() {
+        let mut mesh = Mesh::new("assets/cube.obj");
+        mesh.transform(&Mat::translate(Vct::new(1.0, -2.0, 0.5)));
+        assert!((mesh.area() - 6.0).abs() < EPS && (mesh.signed_volume() - 1.0).abs() < EPS);
+        assert!((mesh.centroid() - Vct::new(1.5, -1.5, 1.0)).len() < EPS);
+        assert!((mesh.center_of_mass() - Vct::new(1.5, -1.5, 1.0)).len() < EPS);
+        // 单位立方体绕过中心的轴的转动惯量为 1/6
+        let i = mesh.inertia();
+        for r in 0..3 {
+            for c in 0..3 {
+                assert!((i[r][c] - if r == c { 1.0 / 6.0 } else { 0.0 }).abs() < EPS);
+            }
+        }
+        let e = mesh.edge_lengths();
+        assert_eq!(e.count, 18);
+        assert!((e.min - 1.0).abs() < EPS && (e.max - Flt::sqrt(2.0)).abs() < EPS);
+        let q = mesh.triangle_quality();
+        assert_eq!((q.aspect.total(), q.aspect.counts[0], q.min_angle.counts[4]), (12, 12, 12));
+        assert!((q.smallest_angle - 45.0).abs() < EPS);
+        let (a, b) = (Vct::zero(), Vct::new(1.0, 0.0, 0.0));
+        let c = Vct::new(0.5, Flt::sqrt(3.0) / 2.0, 0.0);
+        assert!(
+            (aspect_ratio(a, b, c) - 1.0).abs() < EPS && (min_angle(a, b, c) - 60.0).abs() < EPS
+        );
+        assert_eq!((aspect_ratio(a, b, b), min_angle(a, b, a + b)), (Flt::INFINITY, 0.0));
+    }
+
+    #[test]
+    fn histogram() {
+        let mut h = Histogram::new(vec![0.0, 1.0, 2.0]);
+        [-1.0, 0.5, 1.0, 5.0].iter().for_each(|&x| h.add(x));
+        assert_eq!(h.counts, vec![2, 2]);
+        for bounds in [vec![], vec![3.0]] {
+            let mut h = Histogram::new(bounds);
+            h.add(1.0);
+            assert_eq!((h.bounds.len(), h.counts.clone()), (2, vec![1]));
+        }
+    }
+
+    #[test]
+    fn distance() {
+        let (a, b, c) = (Vct::zero(), Vct::new(1.0, 0.0, 0.0), Vct::new(0.0, 1.0, 0.0));
+        let p = closest_on_tri(Vct::new(0.2, 0.2, 1.0), a, b, c);
+        assert!((p - Vct::new(0.2, 0.2, 0.0)).len() < EPS);
+        assert_eq!(closest_on_tri(Vct::new(-1.0, -1.0, 0.0), a, b, c), a);
+        assert_eq!(closest_on_tri(Vct::new(0.5, -1.0, 0.0), a, b, c), Vct::new(0.5, 0.0, 0.0));
+        let mesh = Mesh::new("assets/block.obj");
+        assert!(mesh.hausdorff(&mesh, 2).max < EPS);
+        let out = mesh.simplify(0.2);
+        let d = mesh.hausdorff(&out, 2);
+        let diag = mesh.bbox().diagonal();
+        assert!(d.max > 0.0 && d.max < diag * 0.05 && d.mean <= d.rms && d.rms <= d.max);
+    }
+}
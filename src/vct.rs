@@ -1,8 +1,8 @@
-use crate::Flt;
+use crate::float::Float;
 use std::fmt;
 use std::ops::{Add, AddAssign, Sub, SubAssign};
 use std::ops::{Div, DivAssign, Mul, MulAssign};
-use std::ops::{Index, Neg, Rem};
+use std::ops::{Index, IndexMut, Neg, Rem};
 
 /*
    y
@@ -16,34 +16,39 @@ z
 */
 
 #[derive(Copy, Clone, PartialEq, Debug, Default)]
-pub struct Vct {
-    pub x: Flt,
-    pub y: Flt,
-    pub z: Flt,
+pub struct Vct<T = f64> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
 }
 
-impl Vct {
+impl<T: Float> Vct<T> {
     pub fn zero() -> Self {
-        Self::new(0.0, 0.0, 0.0)
+        Self::new(T::ZERO, T::ZERO, T::ZERO)
     }
 
     pub fn one() -> Self {
-        Self::new(1.0, 1.0, 1.0)
+        Self::new(T::ONE, T::ONE, T::ONE)
     }
 
-    pub fn new(x: Flt, y: Flt, z: Flt) -> Self {
+    pub fn new(x: T, y: T, z: T) -> Self {
         Self { x, y, z }
     }
 
-    pub fn dot(&self, rhs: Self) -> Flt {
+    pub fn cast<U: Float>(&self) -> Vct<U> {
+        let c = |x: T| U::from_f64(x.to_f64());
+        Vct::new(c(self.x), c(self.y), c(self.z))
+    }
+
+    pub fn dot(&self, rhs: Self) -> T {
         self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
     }
 
-    pub fn len2(&self) -> Flt {
+    pub fn len2(&self) -> T {
         self.dot(*self)
     }
 
-    pub fn len(&self) -> Flt {
+    pub fn len(&self) -> T {
         self.len2().sqrt()
     }
 
@@ -58,9 +63,56 @@ impl Vct {
     pub fn max(&self, rhs: Self) -> Self {
         Self { x: self.x.max(rhs.x), y: self.y.max(rhs.y), z: self.z.max(rhs.z) }
     }
+
+    pub fn abs(&self) -> Self {
+        self.map(|x| x.abs())
+    }
+
+    pub fn map(&self, f: impl Fn(T) -> T) -> Self {
+        Self::new(f(self.x), f(self.y), f(self.z))
+    }
+
+    // t = 0 时为 self，t = 1 时为 rhs
+    pub fn lerp(&self, rhs: Self, t: T) -> Self {
+        *self + (rhs - *self) * t
+    }
+
+    pub fn distance(&self, rhs: Self) -> T {
+        (*self - rhs).len()
+    }
+
+    // 与 rhs 的夹角（弧度），有零向量时为 0
+    pub fn angle(&self, rhs: Self) -> T {
+        let d = self.len() * rhs.len();
+        if d > T::ZERO {
+            (self.dot(rhs) / d).clamp(-T::ONE, T::ONE).acos()
+        } else {
+            T::ZERO
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> {
+        IntoIterator::into_iter([self.x, self.y, self.z])
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        IntoIterator::into_iter([&mut self.x, &mut self.y, &mut self.z])
+    }
 }
 
-impl fmt::Display for Vct {
+impl<T> From<[T; 3]> for Vct<T> {
+    fn from([x, y, z]: [T; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl<T> From<Vct<T>> for [T; 3] {
+    fn from(v: Vct<T>) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl<T: Float> fmt::Display for Vct<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "({}, {}, {})", self.x, self.y, self.z)
     }
@@ -68,21 +120,21 @@ impl fmt::Display for Vct {
 
 /* Add */
 
-impl Add<Vct> for Vct {
+impl<T: Float> Add<Vct<T>> for Vct<T> {
     type Output = Self;
     fn add(self, rhs: Self) -> Self {
         Self::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
     }
 }
 
-impl Add<Flt> for Vct {
+impl<T: Float> Add<T> for Vct<T> {
     type Output = Self;
-    fn add(self, rhs: Flt) -> Self {
+    fn add(self, rhs: T) -> Self {
         Self::new(self.x + rhs, self.y + rhs, self.z + rhs)
     }
 }
 
-impl AddAssign<Vct> for Vct {
+impl<T: Float> AddAssign<Vct<T>> for Vct<T> {
     fn add_assign(&mut self, rhs: Self) {
         self.x += rhs.x;
         self.y += rhs.y;
@@ -90,8 +142,8 @@ impl AddAssign<Vct> for Vct {
     }
 }
 
-impl AddAssign<Flt> for Vct {
-    fn add_assign(&mut self, rhs: Flt) {
+impl<T: Float> AddAssign<T> for Vct<T> {
+    fn add_assign(&mut self, rhs: T) {
         self.x += rhs;
         self.y += rhs;
         self.z += rhs;
@@ -100,21 +152,21 @@ impl AddAssign<Flt> for Vct {
 
 /* Sub */
 
-impl Sub<Vct> for Vct {
+impl<T: Float> Sub<Vct<T>> for Vct<T> {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self {
         Self::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
     }
 }
 
-impl Sub<Flt> for Vct {
+impl<T: Float> Sub<T> for Vct<T> {
     type Output = Self;
-    fn sub(self, rhs: Flt) -> Self {
+    fn sub(self, rhs: T) -> Self {
         Self::new(self.x - rhs, self.y - rhs, self.z - rhs)
     }
 }
 
-impl SubAssign<Vct> for Vct {
+impl<T: Float> SubAssign<Vct<T>> for Vct<T> {
     fn sub_assign(&mut self, rhs: Self) {
         self.x -= rhs.x;
         self.y -= rhs.y;
@@ -122,8 +174,8 @@ impl SubAssign<Vct> for Vct {
     }
 }
 
-impl SubAssign<Flt> for Vct {
-    fn sub_assign(&mut self, rhs: Flt) {
+impl<T: Float> SubAssign<T> for Vct<T> {
+    fn sub_assign(&mut self, rhs: T) {
         self.x -= rhs;
         self.y -= rhs;
         self.z -= rhs;
@@ -132,21 +184,21 @@ impl SubAssign<Flt> for Vct {
 
 /* Mul */
 
-impl Mul<Vct> for Vct {
+impl<T: Float> Mul<Vct<T>> for Vct<T> {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self {
         Self::new(self.x * rhs.x, self.y * rhs.y, self.z * rhs.z)
     }
 }
 
-impl Mul<Flt> for Vct {
+impl<T: Float> Mul<T> for Vct<T> {
     type Output = Self;
-    fn mul(self, rhs: Flt) -> Self {
+    fn mul(self, rhs: T) -> Self {
         Self::new(self.x * rhs, self.y * rhs, self.z * rhs)
     }
 }
 
-impl MulAssign<Vct> for Vct {
+impl<T: Float> MulAssign<Vct<T>> for Vct<T> {
     fn mul_assign(&mut self, rhs: Self) {
         self.x *= rhs.x;
         self.y *= rhs.y;
@@ -154,8 +206,8 @@ impl MulAssign<Vct> for Vct {
     }
 }
 
-impl MulAssign<Flt> for Vct {
-    fn mul_assign(&mut self, rhs: Flt) {
+impl<T: Float> MulAssign<T> for Vct<T> {
+    fn mul_assign(&mut self, rhs: T) {
         self.x *= rhs;
         self.y *= rhs;
         self.z *= rhs;
@@ -164,21 +216,21 @@ impl MulAssign<Flt> for Vct {
 
 /* Div */
 
-impl Div<Vct> for Vct {
+impl<T: Float> Div<Vct<T>> for Vct<T> {
     type Output = Self;
     fn div(self, rhs: Self) -> Self {
         Self::new(self.x / rhs.x, self.y / rhs.y, self.z / rhs.z)
     }
 }
 
-impl Div<Flt> for Vct {
+impl<T: Float> Div<T> for Vct<T> {
     type Output = Self;
-    fn div(self, rhs: Flt) -> Self {
+    fn div(self, rhs: T) -> Self {
         Self::new(self.x / rhs, self.y / rhs, self.z / rhs)
     }
 }
 
-impl DivAssign<Vct> for Vct {
+impl<T: Float> DivAssign<Vct<T>> for Vct<T> {
     fn div_assign(&mut self, rhs: Self) {
         self.x /= rhs.x;
         self.y /= rhs.y;
@@ -186,8 +238,8 @@ impl DivAssign<Vct> for Vct {
     }
 }
 
-impl DivAssign<Flt> for Vct {
-    fn div_assign(&mut self, rhs: Flt) {
+impl<T: Float> DivAssign<T> for Vct<T> {
+    fn div_assign(&mut self, rhs: T) {
         self.x /= rhs;
         self.y /= rhs;
         self.z /= rhs;
@@ -196,7 +248,7 @@ impl DivAssign<Flt> for Vct {
 
 /* Neg */
 
-impl Neg for Vct {
+impl<T: Float> Neg for Vct<T> {
     type Output = Self;
     fn neg(self) -> Self {
         Self::new(-self.x, -self.y, -self.z)
@@ -205,7 +257,7 @@ impl Neg for Vct {
 
 /* Cross(Rem) */
 
-impl Rem<Vct> for Vct {
+impl<T: Float> Rem<Vct<T>> for Vct<T> {
     type Output = Self;
     fn rem(self, rhs: Self) -> Self {
         Self::new(
@@ -217,22 +269,43 @@ impl Rem<Vct> for Vct {
 }
 
 /* Index */
-impl Index<usize> for Vct {
-    type Output = Flt;
-    fn index(&self, idx: usize) -> &Flt {
-        match idx {
-            0 => &self.x,
-            1 => &self.y,
-            2 => &self.z,
-            3 => &1.0,
-            _ => panic!("invalid index"),
+
+// 下标 3 是齐次坐标，返回常量的引用只能对具体的类型实现
+macro_rules! index {
+    ($t:ident) => {
+        impl Index<usize> for Vct<$t> {
+            type Output = $t;
+            fn index(&self, idx: usize) -> &$t {
+                match idx {
+                    0 => &self.x,
+                    1 => &self.y,
+                    2 => &self.z,
+                    3 => &1.0,
+                    _ => panic!("invalid index"),
+                }
+            }
         }
-    }
+
+        impl IndexMut<usize> for Vct<$t> {
+            fn index_mut(&mut self, idx: usize) -> &mut $t {
+                match idx {
+                    0 => &mut self.x,
+                    1 => &mut self.y,
+                    2 => &mut self.z,
+                    _ => panic!("invalid index"),
+                }
+            }
+        }
+    };
 }
 
+index!(f32);
+index!(f64);
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Flt;
 
     fn gen() -> (Vct, Vct) {
         (Vct::new(1., 2., 3.), Vct::new(2., 3., 4.))
@@ -282,6 +355,23 @@ mod tests {
         assert_eq!(x, c);
     }
 
+    #[test]
+    fn helpers() {
+        let (x, y) = gen();
+        assert_eq!(x.lerp(y, 0.5), Vct::new(1.5, 2.5, 3.5));
+        assert_eq!(x.distance(y), Flt::sqrt(3.0));
+        assert_eq!(Vct::new(-1.0, 2.0, -0.5).abs(), Vct::new(1.0, 2.0, 0.5));
+        let a = Vct::new(1.0, 0.0, 0.0).angle(Vct::new(0.0, 3.0, 0.0));
+        assert!((a - crate::PI / 2.0).abs() < crate::EPS);
+        assert_eq!(Vct::zero().angle(x), 0.0);
+        let mut z = x;
+        z[1] = 5.0;
+        z.iter_mut().for_each(|c| *c *= 2.0);
+        assert_eq!(z.iter().collect::<Vec<_>>(), vec![2.0, 10.0, 6.0]);
+        assert_eq!(<[Flt; 3]>::from(z), [z[0], z[1], z[2]]);
+        assert_eq!(Vct::from([1.0, 2.0, 3.0]), x);
+    }
+
     #[test]
     fn neg() {
         let (x, _) = gen();